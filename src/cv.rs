@@ -1,43 +1,69 @@
-use opencv::{core, prelude::*, videoio};
-
-pub use matrix::Matrix;
-
-pub mod matrix;
-pub struct CV(videoio::VideoCapture);
-
-// Resolution => 640 x 480
-impl CV {
-    pub fn new() -> crate::Result<Self> {
-        //https://docs.opencv.org/3.4/d4/d15/group__videoio__flags__base.html
-        let cam =
-            videoio::VideoCapture::new(0, videoio::CAP_DSHOW).map_err(crate::Error::CVError)?;
-
-        if !cam.is_opened().map_err(crate::Error::CVError)? {
-            return Err(crate::Error::UnknownError(
-                "Unable to open default camera".into(),
-            ));
-        }
-
-        Ok(Self(cam))
-    }
-
-    pub fn get_frame(&mut self) -> crate::Result<Matrix> {
-        let mut frame = core::Mat::default();
-        self.read(&mut frame).map_err(crate::Error::CVError)?;
-        Ok(frame.into())
-    }
-}
-
-impl std::ops::Deref for CV {
-    type Target = videoio::VideoCapture;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl std::ops::DerefMut for CV {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
+use opencv::{core, prelude::*, videoio};
+
+pub use camera::{
+    list_devices, CameraBackend, CameraConfig, CameraDevice, CameraInfo, CaptureFormat,
+};
+pub use matrix::Matrix;
+pub use source::{FrameSource, InputSource, SourceFrame};
+
+pub mod camera;
+pub mod matrix;
+pub mod source;
+
+pub struct CV {
+    capture: videoio::VideoCapture,
+    format: CaptureFormat,
+    mismatches: Vec<String>,
+}
+
+impl CV {
+    pub fn new(config: &CameraConfig) -> crate::Result<Self> {
+        let mut cam = config.device.open(config.backend).inspect_err(|_| {
+            let devices = list_devices(config.backend);
+            tracing::warn!("Available capture devices: {:?}", devices);
+        })?;
+        tracing::info!(
+            "Opened {} with {} backend",
+            config.device,
+            cam.get_backend_name().unwrap_or_default()
+        );
+        let format = config.apply(&mut cam)?;
+        let mismatches = config.mismatches(&format);
+
+        Ok(Self {
+            capture: cam,
+            format,
+            mismatches,
+        })
+    }
+
+    /// Negotiated resolution, fps & pixel format
+    pub fn format(&self) -> &CaptureFormat {
+        &self.format
+    }
+
+    /// Requested settings the camera refused, see `CameraConfig::mismatches`
+    pub fn mismatches(&self) -> &[String] {
+        &self.mismatches
+    }
+
+    pub fn get_frame(&mut self) -> crate::Result<Matrix> {
+        let mut frame = core::Mat::default();
+        self.read(&mut frame).map_err(crate::Error::CVError)?;
+        Ok(frame.into())
+    }
+}
+
+impl std::ops::Deref for CV {
+    type Target = videoio::VideoCapture;
+
+    fn deref(&self) -> &Self::Target {
+        &self.capture
+    }
+}
+
+impl std::ops::DerefMut for CV {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.capture
+    }
+}
@@ -0,0 +1,355 @@
+// Capture device & backend selection
+// https://docs.opencv.org/4.x/d4/d15/group__videoio__flags__base.html
+
+use std::path::{Path, PathBuf};
+
+use opencv::{prelude::*, videoio};
+
+use crate::{Error, Result};
+
+// devices probed when the platform has no device listing
+const PROBE_DEVICES: i32 = 8;
+
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub backend: CameraBackend,
+    pub device: CameraDevice,
+    /// (w, h) requested on open, driver default when unset
+    pub resolution: Option<(u32, u32)>,
+    pub fps: Option<f32>,
+    /// Pixel format like `MJPG` or `YUYV`, MJPG usually unlocks higher resolution & fps over usb
+    pub fourcc: Option<String>,
+}
+
+/// Capture format the camera agreed to
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CaptureFormat {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+    pub fourcc: String,
+}
+
+impl std::fmt::Display for CaptureFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} @ {:.1}fps {}",
+            self.width, self.height, self.fps, self.fourcc
+        )
+    }
+}
+
+impl CameraConfig {
+    /// Requests the configured format, fourcc first as it limits the available sizes
+    pub fn apply(&self, capture: &mut videoio::VideoCapture) -> Result<CaptureFormat> {
+        let mut set = |prop: i32, value: f64, name: &str| -> Result<()> {
+            if !capture.set(prop, value).map_err(Error::CVError)? {
+                tracing::warn!("Camera rejected {} = {}", name, value);
+            }
+            Ok(())
+        };
+        if let Some(fourcc) = &self.fourcc {
+            set(
+                videoio::CAP_PROP_FOURCC,
+                encode_fourcc(fourcc)? as f64,
+                "fourcc",
+            )?;
+        }
+        if let Some((w, h)) = self.resolution {
+            set(videoio::CAP_PROP_FRAME_WIDTH, w as f64, "width")?;
+            set(videoio::CAP_PROP_FRAME_HEIGHT, h as f64, "height")?;
+        }
+        if let Some(fps) = self.fps {
+            set(videoio::CAP_PROP_FPS, fps as f64, "fps")?;
+        }
+
+        let get = |prop: i32| capture.get(prop).map_err(Error::CVError);
+        let negotiated = CaptureFormat {
+            width: get(videoio::CAP_PROP_FRAME_WIDTH)? as u32,
+            height: get(videoio::CAP_PROP_FRAME_HEIGHT)? as u32,
+            fps: get(videoio::CAP_PROP_FPS)? as f32,
+            fourcc: decode_fourcc(get(videoio::CAP_PROP_FOURCC)? as i32),
+        };
+
+        tracing::info!("Camera capture format: {}", negotiated);
+        for mismatch in self.mismatches(&negotiated) {
+            tracing::warn!("Camera refused requested {}", mismatch);
+        }
+        Ok(negotiated)
+    }
+
+    /// Requested values the camera didn't agree to
+    pub fn mismatches(&self, negotiated: &CaptureFormat) -> Vec<String> {
+        let mut mismatches = vec![];
+        if let Some((w, h)) = self.resolution {
+            if (w, h) != (negotiated.width, negotiated.height) {
+                mismatches.push(format!(
+                    "resolution {}x{}, got {}x{}",
+                    w, h, negotiated.width, negotiated.height
+                ));
+            }
+        }
+        if let Some(fps) = self.fps {
+            // drivers report 29.97 for 30 etc
+            if (fps - negotiated.fps).abs() > 0.5 {
+                mismatches.push(format!("fps {}, got {}", fps, negotiated.fps));
+            }
+        }
+        if let Some(fourcc) = &self.fourcc {
+            if !fourcc.eq_ignore_ascii_case(&negotiated.fourcc) {
+                mismatches.push(format!("fourcc {}, got {}", fourcc, negotiated.fourcc));
+            }
+        }
+        mismatches
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum CameraBackend {
+    /// V4L2 on linux, DirectShow on windows, AVFoundation on macos
+    #[default]
+    Auto,
+    /// OpenCV's own pick
+    Any,
+    V4l2,
+    GStreamer,
+    DShow,
+    Msmf,
+    AVFoundation,
+}
+
+impl CameraBackend {
+    /// OpenCV api preference
+    pub fn api(&self) -> i32 {
+        match self {
+            CameraBackend::Auto if cfg!(target_os = "linux") => videoio::CAP_V4L2,
+            CameraBackend::Auto if cfg!(target_os = "windows") => videoio::CAP_DSHOW,
+            CameraBackend::Auto if cfg!(target_os = "macos") => videoio::CAP_AVFOUNDATION,
+            CameraBackend::Auto | CameraBackend::Any => videoio::CAP_ANY,
+            CameraBackend::V4l2 => videoio::CAP_V4L2,
+            CameraBackend::GStreamer => videoio::CAP_GSTREAMER,
+            CameraBackend::DShow => videoio::CAP_DSHOW,
+            CameraBackend::Msmf => videoio::CAP_MSMF,
+            CameraBackend::AVFoundation => videoio::CAP_AVFOUNDATION,
+        }
+    }
+}
+
+/// `0` or `"/dev/video0"` in config, any other path (symlink, gstreamer pipeline) is passed through
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum CameraDevice {
+    Index(i32),
+    Path(String),
+}
+
+impl Default for CameraDevice {
+    fn default() -> Self {
+        CameraDevice::Index(0)
+    }
+}
+
+impl std::fmt::Display for CameraDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CameraDevice::Index(idx) => write!(f, "camera {}", idx),
+            CameraDevice::Path(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+impl CameraDevice {
+    /// Device index of `/dev/videoN` paths, following symlinks like `/dev/v4l/by-id/..`
+    pub fn index(&self) -> Option<i32> {
+        match self {
+            CameraDevice::Index(idx) => Some(*idx),
+            CameraDevice::Path(path) => {
+                let path = Path::new(path);
+                video_index(path).or_else(|| path.canonicalize().ok().and_then(|p| video_index(&p)))
+            }
+        }
+    }
+
+    pub fn open(&self, backend: CameraBackend) -> Result<videoio::VideoCapture> {
+        let capture = match (self.index(), self) {
+            (Some(idx), _) => videoio::VideoCapture::new(idx, backend.api()),
+            (None, CameraDevice::Path(path)) => {
+                videoio::VideoCapture::from_file(path, backend.api())
+            }
+            (None, CameraDevice::Index(idx)) => videoio::VideoCapture::new(*idx, backend.api()),
+        }
+        .map_err(Error::CVError)?;
+
+        if !capture.is_opened().map_err(Error::CVError)? {
+            return Err(Error::UnknownError(
+                format!("Unable to open {} with {:?} backend", self, backend).into(),
+            ));
+        }
+        Ok(capture)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraInfo {
+    pub device: CameraDevice,
+    /// Driver reported name, empty when unknown
+    pub name: String,
+}
+
+/// Capture devices present on the system, video4linux listing on linux & probing elsewhere
+pub fn list_devices(backend: CameraBackend) -> Vec<CameraInfo> {
+    if cfg!(target_os = "linux") {
+        if let Some(devices) =
+            list_video4linux(Path::new("/dev"), Path::new("/sys/class/video4linux"))
+        {
+            return devices;
+        }
+    }
+
+    (0..PROBE_DEVICES)
+        .map(CameraDevice::Index)
+        .filter(|device| device.open(backend).is_ok())
+        .map(|device| CameraInfo {
+            device,
+            name: String::new(),
+        })
+        .collect()
+}
+
+// `/dev/videoN` nodes with their sysfs names, `None` without a /dev listing
+fn list_video4linux(dev: &Path, sysfs: &Path) -> Option<Vec<CameraInfo>> {
+    let mut devices = std::fs::read_dir(dev)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| video_index(&path).map(|idx| (idx, path)))
+        .collect::<Vec<(i32, PathBuf)>>();
+    devices.sort_by_key(|(idx, _)| *idx);
+
+    Some(
+        devices
+            .into_iter()
+            .map(|(idx, path)| CameraInfo {
+                device: CameraDevice::Path(path.to_string_lossy().to_string()),
+                name: std::fs::read_to_string(sysfs.join(format!("video{}", idx)).join("name"))
+                    .map(|name| name.trim().to_string())
+                    .unwrap_or_default(),
+            })
+            .collect(),
+    )
+}
+
+/// 4 ascii chars to the little endian code OpenCV uses
+pub fn encode_fourcc(fourcc: &str) -> Result<i32> {
+    let bytes: [u8; 4] = fourcc
+        .as_bytes()
+        .try_into()
+        .ok()
+        .filter(|_| fourcc.is_ascii())
+        .ok_or_else(|| {
+            Error::UnknownError(format!("FOURCC needs 4 ascii characters: {:?}", fourcc).into())
+        })?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+pub fn decode_fourcc(code: i32) -> String {
+    code.to_le_bytes()
+        .iter()
+        .map(|b| match b {
+            0 => ' ',
+            b if b.is_ascii_graphic() => *b as char,
+            _ => '?',
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+fn video_index(path: &Path) -> Option<i32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("video")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_fourcc, encode_fourcc, list_video4linux, CameraConfig, CameraDevice, CaptureFormat,
+    };
+    use crate::testing::TempDir;
+
+    #[test]
+    fn round_trips_fourcc() {
+        let code = encode_fourcc("MJPG").expect("Failed to encode");
+        // cv::VideoWriter::fourcc('M', 'J', 'P', 'G')
+        assert_eq!(code, 0x47504a4d);
+        assert_eq!(decode_fourcc(code), "MJPG");
+        assert_eq!(decode_fourcc(0), "");
+        assert!(encode_fourcc("H264X").is_err());
+    }
+
+    #[test]
+    fn reports_refused_capture_format() {
+        let config = CameraConfig {
+            resolution: Some((1920, 1080)),
+            fps: Some(30.),
+            fourcc: Some("mjpg".into()),
+            ..Default::default()
+        };
+        let agreed = CaptureFormat {
+            width: 1920,
+            height: 1080,
+            fps: 29.97,
+            fourcc: "MJPG".into(),
+        };
+        assert!(config.mismatches(&agreed).is_empty());
+
+        let refused = CaptureFormat {
+            width: 640,
+            height: 480,
+            fps: 15.,
+            fourcc: "YUYV".into(),
+        };
+        assert_eq!(config.mismatches(&refused).len(), 3);
+        assert!(CameraConfig::default().mismatches(&refused).is_empty());
+    }
+
+    #[test]
+    fn parses_device_config() {
+        let index: CameraDevice = serde_json::from_str("2").expect("Failed to parse index");
+        assert_eq!(index.index(), Some(2));
+
+        let path: CameraDevice =
+            serde_json::from_str(r#""/dev/video3""#).expect("Failed to parse path");
+        assert_eq!(path, CameraDevice::Path("/dev/video3".into()));
+        assert_eq!(path.index(), Some(3));
+
+        let pipeline = CameraDevice::Path("v4l2src ! videoconvert ! appsink".into());
+        assert_eq!(pipeline.index(), None);
+    }
+
+    #[test]
+    fn lists_video4linux_devices() {
+        let root = TempDir::new("v4l");
+        let (dev, sysfs) = (root.join("dev"), root.join("sys"));
+        std::fs::create_dir_all(&dev).expect("Failed to create dev");
+        for name in ["video10", "video2", "null", "videox"] {
+            std::fs::write(dev.join(name), "").expect("Failed to create node");
+        }
+        std::fs::create_dir_all(sysfs.join("video2")).expect("Failed to create sysfs");
+        std::fs::write(sysfs.join("video2").join("name"), "Integrated Camera\n")
+            .expect("Failed to write name");
+
+        let devices = list_video4linux(&dev, &sysfs).expect("Failed to list devices");
+
+        assert_eq!(
+            devices.iter().map(|d| d.device.index()).collect::<Vec<_>>(),
+            [Some(2), Some(10)]
+        );
+        assert_eq!(devices[0].name, "Integrated Camera");
+        assert_eq!(devices[1].name, "");
+    }
+}
@@ -0,0 +1,425 @@
+// Frame inputs consumed by the processor & headless runners alike
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use opencv::{core, prelude::*, videoio};
+
+use crate::{image::Image, model::Tensor, Error, Result};
+
+use super::{CameraConfig, CaptureFormat, CV};
+
+// timestamps of sources without a native rate
+const DEFAULT_FRAME_RATE: f32 = 30.;
+
+#[derive(Debug, Clone)]
+pub struct SourceFrame {
+    pub tensor: Tensor,
+    /// 0 based position in the source
+    pub index: usize,
+    /// Since the first frame, from the source's rate or wall clock for live inputs
+    pub timestamp: Duration,
+}
+
+pub trait FrameSource: Send {
+    /// `None` once the source is exhausted
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>>;
+
+    /// Native frames per second when known
+    fn frame_rate(&self) -> Option<f32>;
+
+    /// Total frames when known up front
+    fn frame_count(&self) -> Option<usize> {
+        None
+    }
+
+    /// Format a capture device agreed to, with the requested settings it refused
+    fn capture_format(&self) -> Option<(&CaptureFormat, &[String])> {
+        None
+    }
+}
+
+/// Serializable source selection, see `open`
+#[derive(Debug, Default, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum InputSource {
+    #[default]
+    Camera,
+    Video(PathBuf),
+    Image(PathBuf),
+    /// printf style numbered files, `frames/%04d.png`
+    Sequence {
+        pattern: String,
+        start: usize,
+        fps: Option<f32>,
+    },
+    /// Every image in the directory, sorted by file name
+    Directory {
+        path: PathBuf,
+        fps: Option<f32>,
+    },
+}
+
+impl InputSource {
+    /// `camera` is used by `InputSource::Camera`
+    pub fn open(&self, camera: &CameraConfig) -> Result<Box<dyn FrameSource>> {
+        Ok(match self {
+            InputSource::Camera => Box::new(CameraSource::new(CV::new(camera)?)),
+            InputSource::Video(path) => Box::new(VideoSource::new(path)?),
+            InputSource::Image(path) => Box::new(ImageSource::new(path.clone())),
+            InputSource::Sequence {
+                pattern,
+                start,
+                fps,
+            } => Box::new(SequenceSource::new(pattern.clone(), *start, *fps)?),
+            InputSource::Directory { path, fps } => Box::new(DirectorySource::new(path, *fps)?),
+        })
+    }
+}
+
+pub struct CameraSource {
+    cv: CV,
+    index: usize,
+    started: Option<Instant>,
+}
+
+impl CameraSource {
+    pub fn new(cv: CV) -> Self {
+        Self {
+            cv,
+            index: 0,
+            started: None,
+        }
+    }
+}
+
+impl FrameSource for CameraSource {
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+        let mat = self.cv.get_frame()?;
+        if mat.empty() {
+            return Err(Error::UnknownError("Camera returned an empty frame".into()));
+        }
+
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let frame = SourceFrame {
+            tensor: mat.into(),
+            index: self.index,
+            timestamp: started.elapsed(),
+        };
+        self.index += 1;
+        Ok(Some(frame))
+    }
+
+    fn frame_rate(&self) -> Option<f32> {
+        Some(self.cv.format().fps).filter(|fps| fps.is_normal() && *fps > 0.)
+    }
+
+    fn capture_format(&self) -> Option<(&CaptureFormat, &[String])> {
+        Some((self.cv.format(), self.cv.mismatches()))
+    }
+}
+
+pub struct VideoSource {
+    capture: videoio::VideoCapture,
+    index: usize,
+    fps: Option<f32>,
+    frame_count: Option<usize>,
+}
+
+impl VideoSource {
+    pub fn new(path: &Path) -> Result<Self> {
+        let capture = videoio::VideoCapture::from_file(&path.to_string_lossy(), videoio::CAP_ANY)
+            .map_err(Error::CVError)?;
+        if !capture.is_opened().map_err(Error::CVError)? {
+            return Err(Error::UnknownError(
+                format!("Unable to open video: {}", path.display()).into(),
+            ));
+        }
+
+        let prop = |id: i32| capture.get(id).ok().filter(|v| v.is_normal() && *v > 0.);
+        Ok(Self {
+            fps: prop(videoio::CAP_PROP_FPS).map(|v| v as f32),
+            frame_count: prop(videoio::CAP_PROP_FRAME_COUNT).map(|v| v as usize),
+            capture,
+            index: 0,
+        })
+    }
+}
+
+impl FrameSource for VideoSource {
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+        let mut mat = core::Mat::default();
+        if !self.capture.read(&mut mat).map_err(Error::CVError)? || mat.empty() {
+            return Ok(None);
+        }
+
+        let frame = SourceFrame {
+            tensor: super::Matrix::from(mat).into(),
+            index: self.index,
+            timestamp: timestamp(self.index, self.fps),
+        };
+        self.index += 1;
+        Ok(Some(frame))
+    }
+
+    fn frame_rate(&self) -> Option<f32> {
+        self.fps
+    }
+
+    fn frame_count(&self) -> Option<usize> {
+        self.frame_count
+    }
+}
+
+/// Single still, yielded once
+pub struct ImageSource(Option<PathBuf>);
+
+impl ImageSource {
+    pub fn new(path: PathBuf) -> Self {
+        Self(Some(path))
+    }
+}
+
+impl FrameSource for ImageSource {
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+        let Some(path) = self.0.take() else {
+            return Ok(None);
+        };
+        Ok(Some(SourceFrame {
+            tensor: Image::from_path(path, None)?.into(),
+            index: 0,
+            timestamp: Duration::ZERO,
+        }))
+    }
+
+    fn frame_rate(&self) -> Option<f32> {
+        None
+    }
+
+    fn frame_count(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// Numbered files, ends at the first missing number
+pub struct SequenceSource {
+    pattern: String,
+    next: usize,
+    index: usize,
+    fps: Option<f32>,
+}
+
+impl SequenceSource {
+    pub fn new(pattern: String, start: usize, fps: Option<f32>) -> Result<Self> {
+        // validates the pattern
+        sequence_path(&pattern, start)?;
+        Ok(Self {
+            pattern,
+            next: start,
+            index: 0,
+            fps: check_fps(fps)?,
+        })
+    }
+}
+
+impl FrameSource for SequenceSource {
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+        let path = sequence_path(&self.pattern, self.next)?;
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let frame = SourceFrame {
+            tensor: Image::from_path(path, None)?.into(),
+            index: self.index,
+            timestamp: timestamp(self.index, self.frame_rate()),
+        };
+        self.next += 1;
+        self.index += 1;
+        Ok(Some(frame))
+    }
+
+    fn frame_rate(&self) -> Option<f32> {
+        Some(self.fps.unwrap_or(DEFAULT_FRAME_RATE))
+    }
+}
+
+pub struct DirectorySource {
+    files: Vec<PathBuf>,
+    index: usize,
+    fps: Option<f32>,
+}
+
+impl DirectorySource {
+    pub fn new(dir: &Path, fps: Option<f32>) -> Result<Self> {
+        let fps = check_fps(fps)?;
+        let mut files = std::fs::read_dir(dir)
+            .map_err(Error::as_unknown_error)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file() && image::ImageFormat::from_path(path).is_ok())
+            .collect::<Vec<PathBuf>>();
+        files.sort();
+
+        Ok(Self {
+            files,
+            index: 0,
+            fps,
+        })
+    }
+}
+
+impl FrameSource for DirectorySource {
+    fn next_frame(&mut self) -> Result<Option<SourceFrame>> {
+        let Some(path) = self.files.get(self.index) else {
+            return Ok(None);
+        };
+
+        let frame = SourceFrame {
+            tensor: Image::from_path(path.clone(), None)?.into(),
+            index: self.index,
+            timestamp: timestamp(self.index, self.frame_rate()),
+        };
+        self.index += 1;
+        Ok(Some(frame))
+    }
+
+    fn frame_rate(&self) -> Option<f32> {
+        Some(self.fps.unwrap_or(DEFAULT_FRAME_RATE))
+    }
+
+    fn frame_count(&self) -> Option<usize> {
+        Some(self.files.len())
+    }
+}
+
+// configured rates, 0 | negative would make timestamps NaN
+fn check_fps(fps: Option<f32>) -> Result<Option<f32>> {
+    match fps {
+        Some(fps) if !(fps.is_normal() && fps > 0.) => Err(Error::UnknownError(
+            format!("Input fps must be a positive number, got {}", fps).into(),
+        )),
+        fps => Ok(fps),
+    }
+}
+
+fn timestamp(index: usize, fps: Option<f32>) -> Duration {
+    Duration::from_secs_f32(index as f32 / fps.unwrap_or(DEFAULT_FRAME_RATE))
+}
+
+// first `%d` | `%0Nd` of `pattern` replaced by `number`
+fn sequence_path(pattern: &str, number: usize) -> Result<PathBuf> {
+    let invalid = || {
+        Error::UnknownError(
+            format!(
+                "Image sequence pattern needs a %d or %0Nd placeholder: {}",
+                pattern
+            )
+            .into(),
+        )
+    };
+    let start = pattern.find('%').ok_or_else(invalid)?;
+    let spec_len = pattern[start + 1..].find('d').ok_or_else(invalid)?;
+    let spec = &pattern[start + 1..start + 1 + spec_len];
+    if !spec.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let width = spec.parse::<usize>().unwrap_or(0);
+    let number = match spec.starts_with('0') {
+        true => format!("{:0width$}", number, width = width),
+        false => format!("{:width$}", number, width = width),
+    };
+    Ok(PathBuf::from(format!(
+        "{}{}{}",
+        &pattern[..start],
+        number,
+        &pattern[start + spec_len + 2..]
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{sequence_path, DirectorySource, FrameSource, SequenceSource};
+    use crate::testing::TempDir;
+
+    fn temp_frames(names: &[&str]) -> TempDir {
+        let dir = TempDir::new("frames");
+        for (idx, name) in names.iter().enumerate() {
+            image::RgbImage::from_pixel(4, 2, image::Rgb([idx as u8 * 50, 0, 0]))
+                .save(dir.join(name))
+                .expect("Failed to save frame");
+        }
+        dir
+    }
+
+    #[test]
+    fn formats_sequence_patterns() {
+        assert_eq!(
+            sequence_path("out/%04d.png", 7).expect("Failed to format"),
+            PathBuf::from("out/0007.png")
+        );
+        assert_eq!(
+            sequence_path("f%d.jpg", 12).expect("Failed to format"),
+            PathBuf::from("f12.jpg")
+        );
+        assert!(sequence_path("frame.png", 0).is_err());
+        assert!(sequence_path("f%s.png", 0).is_err());
+    }
+
+    #[test]
+    fn reads_sequence_until_gap() {
+        let dir = temp_frames(&["f_001.png", "f_002.png", "f_004.png"]);
+        let pattern = dir.join("f_%03d.png").to_string_lossy().to_string();
+
+        let mut source = SequenceSource::new(pattern, 1, Some(10.)).expect("Failed to open");
+        let mut frames = vec![];
+        while let Some(frame) = source.next_frame().expect("Failed to read frame") {
+            frames.push(frame);
+        }
+
+        assert_eq!(frames.len(), 2, "Sequence should stop at the missing 003");
+        assert_eq!(frames[1].index, 1);
+        assert_eq!(frames[1].timestamp.as_millis(), 100);
+        assert_eq!(frames[1].tensor.dim(), (1, 3, 2, 4));
+    }
+
+    #[test]
+    fn rejects_invalid_frame_rates() {
+        let dir = temp_frames(&["a.png"]);
+        let pattern = dir.join("f_%03d.png").to_string_lossy().to_string();
+        for fps in [0., -30., f32::NAN, f32::INFINITY] {
+            assert!(
+                SequenceSource::new(pattern.clone(), 0, Some(fps)).is_err(),
+                "Sequence accepted {} fps",
+                fps
+            );
+            assert!(
+                DirectorySource::new(&dir, Some(fps)).is_err(),
+                "Directory accepted {} fps",
+                fps
+            );
+        }
+        let source = DirectorySource::new(&dir, Some(24.)).expect("Failed to open");
+
+        assert_eq!(source.frame_rate(), Some(24.));
+    }
+
+    #[test]
+    fn reads_directory_in_name_order() {
+        let dir = temp_frames(&["b.png", "a.png", "c.png"]);
+        std::fs::write(dir.join("notes.txt"), "not a frame").expect("Failed to write");
+
+        let mut source = DirectorySource::new(&dir, None).expect("Failed to open");
+        assert_eq!(source.frame_count(), Some(3));
+        let first = source
+            .next_frame()
+            .expect("Failed to read frame")
+            .expect("Missing frame");
+
+        // a.png was saved second, red 50
+        assert_eq!(first.tensor.pixel_rgba(0, 0, 0)[0], 50);
+    }
+}
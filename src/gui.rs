@@ -1,190 +1,326 @@
-use std::time::Duration;
-
-use eframe::egui::{self, Button, Color32, Vec2};
-use messenger::{MessageSeverity, Messenger};
-use proc::{ProcStatus, Processor};
-
-use crate::{error::Error, result::Result, setting::Setting};
-
-mod messenger;
-mod proc;
-
-pub struct Gui {
-    setting: Setting,
-    proc: Processor,
-    messenger: Messenger,
-}
-
-impl eframe::App for Gui {
-    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let proc_status = self.proc.get_status();
-            // Main Control
-            ui.horizontal(|ui| {
-                // Source Image Button
-                ui.vertical(|ui| {
-                    let button_size = Vec2::new(110., 110.);
-                    let image_button = egui::Button::image(
-                        self.proc.get_source_img().fit_to_exact_size(button_size),
-                    )
-                    .min_size(button_size);
-
-                    if ui
-                        .add_enabled(
-                            proc_status != ProcStatus::Previewing
-                                && proc_status != ProcStatus::Running,
-                            image_button,
-                        )
-                        .clicked()
-                    {
-                        let Some(path) = rfd::FileDialog::new().pick_file() else {
-                            self.messenger
-                                .send_message("No files selected", Some(MessageSeverity::Warning));
-                            return;
-                        };
-
-                        if let Err(err) = self.proc.set_source_with_path(path) {
-                            self.messenger
-                                .send_message(err.to_string(), Some(MessageSeverity::Error));
-                        }
-                    }
-                });
-
-                // Preview and Mediate
-                ui.vertical_centered_justified(|ui| {
-                    let size = ui.max_rect().size();
-                    let spacing = ui.spacing().item_spacing;
-                    let (run_btn, preview_btn) = (
-                        ui.add_enabled(
-                            proc_status == ProcStatus::Idle || proc_status == ProcStatus::Running,
-                            Button::new(if proc_status == ProcStatus::Running {
-                                "Stop"
-                            } else {
-                                "Run"
-                            })
-                            .min_size(Vec2::new(100., size.y * 0.5 - spacing.y * 0.5)),
-                        ),
-                        ui.add_enabled(
-                            proc_status == ProcStatus::Idle
-                                || proc_status == ProcStatus::Previewing,
-                            Button::new(if proc_status == ProcStatus::Previewing {
-                                "Stop Preview"
-                            } else {
-                                "Preview"
-                            })
-                            .min_size(Vec2::new(100., size.y * 0.5 - spacing.y * 0.5)),
-                        ),
-                    );
-
-                    if run_btn.clicked() {
-                        println!("run clicked");
-                    }
-
-                    if preview_btn.clicked() {
-                        if proc_status == ProcStatus::Idle {
-                            if let Err(error) = self.proc.run_preview() {
-                                self.messenger.send_message(
-                                    format!("Failed to run with: {}", error),
-                                    Some(MessageSeverity::Error),
-                                );
-                            }
-                        } else {
-                            let _ = self.proc.stop();
-                        }
-                    }
-                });
-            });
-
-            // Image Display
-            egui::Frame::none()
-                .rounding(3.)
-                .stroke(egui::Stroke::new(1., Color32::WHITE))
-                .outer_margin(egui::Margin::symmetric(0., 8.))
-                .inner_margin(egui::Margin::same(2.))
-                .show(ui, |ui| match proc_status {
-                    ProcStatus::Running => {
-                        ui.add_sized(
-                            ui.available_size(),
-                            egui::Label::new("Not yet implemented: Run"),
-                        );
-                    }
-                    ProcStatus::Previewing => {
-                        let Ok(tex) = self.proc.get_frame().inspect_err(|err| {
-                            self.messenger.send_message(
-                                format!("Preview failed with: {}", err),
-                                Some(MessageSeverity::Error),
-                            );
-                        }) else {
-                            return;
-                        };
-                        ui.add_sized(
-                            ui.available_size(),
-                            egui::Image::from_texture(egui::load::SizedTexture::from_handle(&tex))
-                                .max_size(ui.available_size()),
-                        );
-                        ctx.request_repaint()
-                    }
-                    // TODO: Might want Error state msg
-                    _ => {
-                        ui.add_sized(
-                            ui.available_size(),
-                            egui::Label::new("Not yet impleted: Rest"),
-                        );
-                    }
-                });
-        });
-
-        let _ = self.messenger.register_messenger(ctx);
-
-        let _ = self.proc.register_error(|err| {
-            self.messenger
-                .send_message(format!("Processor - {}", err), Some(MessageSeverity::Error));
-        });
-
-        self.setting.update_dim(ctx);
-    }
-}
-
-impl Gui {
-    #[tracing::instrument(name = "Initializing Gui", skip(setting))]
-    pub fn new(setting: Setting) -> Self {
-        let config = setting.config.clone();
-        Self {
-            setting,
-            //TODO: Load these after inital render with loading
-            proc: Processor::new(&config).unwrap(),
-            messenger: Messenger::new(Duration::from_millis(2000)),
-        }
-    }
-
-    #[tracing::instrument(name = "Running Gui", skip(self), err)]
-    pub fn run(mut self) -> Result<()> {
-        let options = eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default()
-                .with_inner_size([
-                    self.setting.config.gui.width,
-                    self.setting.config.gui.height,
-                ])
-                .with_min_inner_size([350., 450.])
-                .with_max_inner_size([750., 850.]),
-            ..Default::default()
-        };
-        eframe::run_native(
-            "noface",
-            options,
-            Box::new(|cc| {
-                // register
-                // TODO: Handle Err
-                let _ = self.proc.register(&cc.egui_ctx);
-
-                egui_extras::install_image_loaders(&cc.egui_ctx);
-                Ok(Box::new(self))
-            }),
-        )
-        .map_err(Error::GuiError)
-    }
-}
-
-pub trait GuiSetting {
-    fn update_dim(&mut self, ctx: &egui::Context);
-}
+use std::time::Duration;
+
+use eframe::egui::{self, Button, Color32, Vec2};
+use messenger::{MessageSeverity, Messenger};
+use proc::{ProcStatus, Processor};
+
+use crate::{error::Error, model::tracker::DetectionStats, result::Result, setting::Setting};
+
+mod messenger;
+mod proc;
+
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "mkv", "avi", "webm", "m4v"];
+
+pub struct Gui {
+    setting: Setting,
+    proc: Processor,
+    messenger: Messenger,
+}
+
+impl eframe::App for Gui {
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let proc_status = self.proc.get_status();
+            // Main Control
+            ui.horizontal(|ui| {
+                // Source Image Button
+                ui.vertical(|ui| {
+                    let button_size = Vec2::new(110., 110.);
+                    let image_button = egui::Button::image(
+                        self.proc.get_source_img().fit_to_exact_size(button_size),
+                    )
+                    .min_size(button_size);
+
+                    if ui
+                        .add_enabled(
+                            proc_status != ProcStatus::Previewing
+                                && proc_status != ProcStatus::Running,
+                            image_button,
+                        )
+                        .clicked()
+                    {
+                        let Some(path) = rfd::FileDialog::new().pick_file() else {
+                            self.messenger
+                                .send_message("No files selected", Some(MessageSeverity::Warning));
+                            return;
+                        };
+
+                        if let Err(err) = self.proc.set_source_with_path(path) {
+                            self.messenger
+                                .send_message(err.to_string(), Some(MessageSeverity::Error));
+                        }
+                    }
+                });
+
+                // Preview and Mediate
+                ui.vertical_centered_justified(|ui| {
+                    let size = ui.max_rect().size();
+                    let spacing = ui.spacing().item_spacing;
+                    let (run_btn, preview_btn) = (
+                        ui.add_enabled(
+                            proc_status == ProcStatus::Idle || proc_status == ProcStatus::Running,
+                            Button::new(if proc_status == ProcStatus::Running {
+                                "Stop"
+                            } else {
+                                "Run"
+                            })
+                            .min_size(Vec2::new(100., size.y * 0.5 - spacing.y * 0.5)),
+                        ),
+                        ui.add_enabled(
+                            proc_status == ProcStatus::Idle
+                                || proc_status == ProcStatus::Previewing,
+                            Button::new(if proc_status == ProcStatus::Previewing {
+                                "Stop Preview"
+                            } else {
+                                "Preview"
+                            })
+                            .min_size(Vec2::new(100., size.y * 0.5 - spacing.y * 0.5)),
+                        ),
+                    );
+
+                    if run_btn.clicked() {
+                        if proc_status == ProcStatus::Idle {
+                            let Some(path) = rfd::FileDialog::new()
+                                .add_filter("video", VIDEO_EXTENSIONS)
+                                .pick_file()
+                            else {
+                                self.messenger.send_message(
+                                    "No video selected",
+                                    Some(MessageSeverity::Warning),
+                                );
+                                return;
+                            };
+
+                            if let Err(error) = self.proc.run_video(path) {
+                                self.messenger.send_message(
+                                    format!("Failed to run with: {}", error),
+                                    Some(MessageSeverity::Error),
+                                );
+                            }
+                        } else {
+                            let _ = self.proc.stop();
+                        }
+                    }
+
+                    if preview_btn.clicked() {
+                        if proc_status == ProcStatus::Idle {
+                            if let Err(error) = self.proc.run_preview() {
+                                self.messenger.send_message(
+                                    format!("Failed to run with: {}", error),
+                                    Some(MessageSeverity::Error),
+                                );
+                            }
+                        } else {
+                            let _ = self.proc.stop();
+                        }
+                    }
+                });
+            });
+
+            // Image Display
+            egui::Frame::none()
+                .rounding(3.)
+                .stroke(egui::Stroke::new(1., Color32::WHITE))
+                .outer_margin(egui::Margin::symmetric(0., 8.))
+                .inner_margin(egui::Margin::same(2.))
+                .show(ui, |ui| match proc_status {
+                    ProcStatus::Running => {
+                        let progress = self.proc.get_progress().unwrap_or_default();
+                        ui.label(match progress.frame_count {
+                            Some(count) => format!(
+                                "{} / {} frames ({:.1} fps)",
+                                progress.frames, count, progress.fps
+                            ),
+                            None => format!("{} frames ({:.1} fps)", progress.frames, progress.fps),
+                        });
+                        detection_label(ui, &self.proc.get_detection_stats());
+                        stream_label(ui, self.proc.get_stream());
+                        if let Ok(tex) = self.proc.get_frame() {
+                            ui.add_sized(
+                                ui.available_size(),
+                                egui::Image::from_texture(egui::load::SizedTexture::from_handle(
+                                    &tex,
+                                ))
+                                .max_size(ui.available_size()),
+                            );
+                        }
+                        ctx.request_repaint()
+                    }
+                    ProcStatus::Previewing => {
+                        ui.horizontal(|ui| {
+                            let recording = self.proc.get_recording();
+                            let record_btn = ui.button(if recording.is_some() {
+                                "Stop Recording"
+                            } else {
+                                "Record"
+                            });
+                            if let Some((elapsed, bytes)) = recording {
+                                let secs = elapsed.as_secs();
+                                ui.label(format!(
+                                    "{:02}:{:02} - {}",
+                                    secs / 60,
+                                    secs % 60,
+                                    format_size(bytes)
+                                ));
+                            }
+                            if !record_btn.clicked() {
+                                return;
+                            }
+
+                            let result = match recording {
+                                Some(_) => self.proc.stop_recording(),
+                                None => match rfd::FileDialog::new()
+                                    .set_file_name(format!(
+                                        "noface_recording.{}",
+                                        self.proc.recording_extension()
+                                    ))
+                                    .save_file()
+                                {
+                                    Some(path) => self.proc.start_recording(path),
+                                    None => Ok(()),
+                                },
+                            };
+                            if let Err(err) = result {
+                                self.messenger.send_message(
+                                    format!("Recording failed with: {}", err),
+                                    Some(MessageSeverity::Error),
+                                );
+                            }
+                        });
+                        detection_label(ui, &self.proc.get_detection_stats());
+                        stream_label(ui, self.proc.get_stream());
+                        let Ok(tex) = self.proc.get_frame().inspect_err(|err| {
+                            self.messenger.send_message(
+                                format!("Preview failed with: {}", err),
+                                Some(MessageSeverity::Error),
+                            );
+                        }) else {
+                            return;
+                        };
+                        ui.add_sized(
+                            ui.available_size(),
+                            egui::Image::from_texture(egui::load::SizedTexture::from_handle(&tex))
+                                .max_size(ui.available_size()),
+                        );
+                        ctx.request_repaint()
+                    }
+                    // TODO: Might want Error state msg
+                    _ => {
+                        ui.add_sized(
+                            ui.available_size(),
+                            egui::Label::new("Not yet impleted: Rest"),
+                        );
+                    }
+                });
+        });
+
+        // preview ended on its own, e.g. a finished video
+        if self.proc.get_status() != ProcStatus::Previewing && self.proc.get_recording().is_some() {
+            if let Err(err) = self.proc.stop_recording() {
+                self.messenger.send_message(
+                    format!("Recording failed with: {}", err),
+                    Some(MessageSeverity::Error),
+                );
+            }
+        }
+
+        let flushing = self.proc.register_recording(|err| {
+            self.messenger.send_message(
+                format!("Recording failed with: {}", err),
+                Some(MessageSeverity::Error),
+            );
+        });
+        if flushing {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        self.proc.register_notice(|severity, notice| {
+            self.messenger.send_message(notice, Some(severity));
+        });
+
+        let _ = self.messenger.register_messenger(ctx);
+
+        let _ = self.proc.register_error(|err| {
+            self.messenger
+                .send_message(format!("Processor - {}", err), Some(MessageSeverity::Error));
+        });
+
+        self.setting.update_dim(ctx);
+    }
+}
+
+impl Gui {
+    #[tracing::instrument(name = "Initializing Gui", skip(setting))]
+    pub fn new(setting: Setting) -> Self {
+        let config = setting.config.clone();
+        Self {
+            setting,
+            //TODO: Load these after inital render with loading
+            proc: Processor::new(&config).unwrap(),
+            messenger: Messenger::new(Duration::from_millis(2000)),
+        }
+    }
+
+    #[tracing::instrument(name = "Running Gui", skip(self), err)]
+    pub fn run(mut self) -> Result<()> {
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_inner_size([
+                    self.setting.config.gui.width,
+                    self.setting.config.gui.height,
+                ])
+                .with_min_inner_size([350., 450.])
+                .with_max_inner_size([750., 850.]),
+            ..Default::default()
+        };
+        eframe::run_native(
+            "noface",
+            options,
+            Box::new(|cc| {
+                // register
+                // TODO: Handle Err
+                let _ = self.proc.register(&cc.egui_ctx);
+
+                egui_extras::install_image_loaders(&cc.egui_ctx);
+                Ok(Box::new(self))
+            }),
+        )
+        .map_err(Error::GuiError)
+    }
+}
+
+// frames that ran detection & the speed up of propagating the rest
+fn detection_label(ui: &mut egui::Ui, stats: &DetectionStats) {
+    if stats.frames == 0 {
+        return;
+    }
+    ui.label(format!(
+        "detected {} / {} frames ({} roi), {:.1} fps, x{:.2}",
+        stats.detections + stats.roi_detections,
+        stats.frames,
+        stats.roi_detections,
+        stats.fps(),
+        stats.fps_gain()
+    ));
+}
+
+fn stream_label(ui: &mut egui::Ui, stream: Option<(std::net::SocketAddr, usize)>) {
+    if let Some((addr, clients)) = stream {
+        ui.label(format!(
+            "streaming on http://{}/ ({} watching)",
+            addr, clients
+        ));
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1_000_000 => format!("{:.1} KB", bytes as f32 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1} MB", bytes as f32 / 1e6),
+        _ => format!("{:.2} GB", bytes as f32 / 1e9),
+    }
+}
+
+pub trait GuiSetting {
+    fn update_dim(&mut self, ctx: &egui::Context);
+}
@@ -1,210 +1,425 @@
-use crate::{cv::CV, image::Image, model::Model, sync::ResultWorker, Error, Result};
-use std::sync::{Arc, Mutex, RwLock};
-
-mod frame;
-mod source;
-
-// 30 FPS -> 33ms
-const FRAME_DELAY: u64 = 33;
-
-const LOADING_GIF: eframe::egui::ImageSource<'_> =
-    eframe::egui::include_image!("../assets/loading.gif");
-const PROFILE_ICON: eframe::egui::ImageSource<'_> =
-    eframe::egui::include_image!("../assets/profile.svg");
-
-#[derive(Clone, PartialEq, Eq)]
-pub enum ProcStatus {
-    NotInitialized,
-    Processing,
-    Idle,
-    Previewing,
-    Running,
-}
-
-pub struct Processor {
-    pub status: Arc<RwLock<ProcStatus>>,
-    pub model: Arc<Mutex<Model>>,
-    pub source: Arc<RwLock<source::Source>>,
-    pub frame: Arc<RwLock<frame::Frame>>,
-    worker: ResultWorker<Result<()>>,
-}
-
-impl Processor {
-    #[tracing::instrument(name = "Initializing Gui Processor", skip(config), err)]
-    pub fn new(config: &crate::setting::Config) -> Result<Self> {
-        Ok(Self {
-            status: Arc::new(RwLock::new(ProcStatus::NotInitialized)),
-            model: Arc::new(Mutex::new(Model::new(&config.model)?)),
-            source: Arc::new(RwLock::new(source::Source::default())),
-            frame: Arc::new(RwLock::new(frame::Frame::default())),
-            worker: ResultWorker::new("proc_worker"),
-        })
-    }
-
-    pub fn register(&mut self, ctx: &eframe::egui::Context) -> Result<()> {
-        {
-            self.source
-                .write()
-                .map_err(Error::as_guard_error)?
-                .register(ctx);
-        }
-        {
-            self.frame
-                .write()
-                .map_err(Error::as_guard_error)?
-                .register(ctx)
-        }
-        Ok(())
-    }
-
-    pub fn get_status(&self) -> ProcStatus {
-        match self.status.read() {
-            Ok(s) => s.clone(),
-            Err(_) => ProcStatus::Idle,
-        }
-    }
-
-    pub fn set_status(&self, status: ProcStatus) -> Result<()> {
-        *self.status.write().map_err(Error::as_guard_error)? = status;
-        Ok(())
-    }
-
-    pub fn get_source_img(&self) -> eframe::egui::Image {
-        use eframe::egui;
-        let status = self.get_status();
-        match status {
-            ProcStatus::Processing => egui::Image::new(LOADING_GIF),
-            ProcStatus::Idle | ProcStatus::Previewing | ProcStatus::Running => {
-                let src_binding = { self.source.read().map_err(Error::as_guard_error) };
-                let Ok(src) = src_binding.as_deref() else {
-                    return egui::Image::new(PROFILE_ICON);
-                };
-                egui::Image::from_texture(egui::load::SizedTexture::from_handle(&src.texture))
-            }
-            _ => egui::Image::new(PROFILE_ICON),
-        }
-    }
-
-    pub fn get_frame(&self) -> Result<eframe::egui::TextureHandle> {
-        Ok(self
-            .frame
-            .read()
-            .inspect_err(|_| {
-                let _ = self.set_status(ProcStatus::Idle);
-            })
-            .map_err(Error::as_guard_error)?
-            .clone())
-    }
-
-    pub fn set_source_with_path(&mut self, path: std::path::PathBuf) -> Result<()> {
-        self.set_status(ProcStatus::Processing)?;
-        let (stataus, source, model) = (
-            Arc::clone(&self.status),
-            Arc::clone(&self.source),
-            Arc::clone(&self.model),
-        );
-
-        self.worker.send(move || {
-            let img = Image::from_path(path, None)?;
-            let (tensor, vec_tensor) = {
-                model
-                    .lock()
-                    .map_err(Error::as_guard_error)?
-                    .vectorize_tensor(img.into())?
-            };
-            {
-                source
-                    .write()
-                    .map_err(Error::as_guard_error)?
-                    .set_from_tensor(tensor, vec_tensor);
-            }
-            {
-                *stataus.write().map_err(Error::as_guard_error)? = ProcStatus::Idle;
-            }
-            Ok(())
-        })
-    }
-
-    pub fn run_preview(&mut self) -> Result<()> {
-        use std::time::{Duration, Instant};
-        self.set_status(ProcStatus::Previewing)?;
-        let (status, frame, source, model) = (
-            Arc::clone(&self.status),
-            Arc::clone(&self.frame),
-            Arc::clone(&self.source),
-            Arc::clone(&self.model),
-        );
-
-        self.worker.send(move || {
-            let mut cv = CV::new()?;
-            loop {
-                {
-                    if *status.read().map_err(Error::as_guard_error)? != ProcStatus::Previewing {
-                        frame
-                            .write()
-                            .map_err(Error::as_guard_error)?
-                            .set(crate::image::Image::default(), Default::default());
-                        break;
-                    }
-                }
-                let start_inst = Instant::now();
-                let mat = cv.get_frame()?;
-
-                // Processing Starts
-                let src = { source.read().map_err(Error::as_guard_error)?.data.clone() };
-                let data = {
-                    model
-                        .lock()
-                        .map_err(Error::as_guard_error)?
-                        .run(mat.into(), src.into())?
-                };
-                // Processing Ends
-
-                {
-                    frame
-                        .write()
-                        .map_err(Error::as_guard_error)?
-                        .set(data, Default::default());
-                }
-
-                let duration_since = Instant::now().duration_since(start_inst);
-                if Duration::from_millis(FRAME_DELAY) > duration_since {
-                    std::thread::sleep(Duration::from_millis(FRAME_DELAY) - duration_since)
-                }
-            }
-            Ok(())
-        })
-    }
-
-    pub fn stop(&mut self) -> Result<()> {
-        self.set_status(ProcStatus::Idle)
-    }
-
-    pub fn register_error<F>(&mut self, f: F) -> Result<()>
-    where
-        F: FnOnce(Error),
-    {
-        let responds = match self.worker.try_recv() {
-            Ok(res) => res,
-            Err(err) => {
-                if std::sync::mpsc::TryRecvError::Empty == err {
-                    return Ok(());
-                }
-                self.set_status(ProcStatus::Idle)?;
-                f(Error::as_sync_error(err));
-                return Ok(());
-            }
-        };
-        if let Err(received_err) = responds {
-            self.set_status(ProcStatus::Idle)?;
-            f(received_err);
-        }
-        Ok(())
-    }
-}
-
-impl Drop for Processor {
-    fn drop(&mut self) {
-        let _ = self.set_status(ProcStatus::Idle);
-    }
-}
+use crate::{
+    cv::{CameraConfig, CaptureFormat, InputSource},
+    image::Image,
+    job::{VideoConfig, VideoJob, VideoProgress},
+    model::{tracker::DetectionStats, Model},
+    stream::MjpegStream,
+    sync::ResultWorker,
+    Error, Result,
+};
+use std::sync::{Arc, Mutex, RwLock};
+
+use super::messenger::MessageSeverity;
+
+mod frame;
+mod record;
+mod source;
+
+// 30 FPS -> 33ms
+const FRAME_DELAY: u64 = 33;
+const RECORD_FRAME_RATE: f32 = 1000. / FRAME_DELAY as f32;
+
+const LOADING_GIF: eframe::egui::ImageSource<'_> =
+    eframe::egui::include_image!("../assets/loading.gif");
+const PROFILE_ICON: eframe::egui::ImageSource<'_> =
+    eframe::egui::include_image!("../assets/profile.svg");
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum ProcStatus {
+    NotInitialized,
+    Processing,
+    Idle,
+    Previewing,
+    Running,
+}
+
+pub struct Processor {
+    pub status: Arc<RwLock<ProcStatus>>,
+    pub model: Arc<Mutex<Model>>,
+    pub source: Arc<RwLock<source::Source>>,
+    pub frame: Arc<RwLock<frame::Frame>>,
+    pub progress: Arc<RwLock<Option<VideoProgress>>>,
+    pub detection: Arc<RwLock<DetectionStats>>,
+    pub recording: Arc<Mutex<Option<record::Recording>>>,
+    // stream address or failure & negotiated camera format, each shown once
+    notice: Arc<Mutex<Option<(MessageSeverity, String)>>>,
+    // stopped recordings still flushing, polled so the ui never waits on the writer
+    finishing: Vec<record::Recording>,
+    input: InputSource,
+    camera: CameraConfig,
+    video: VideoConfig,
+    stream: Option<Arc<MjpegStream>>,
+    worker: ResultWorker<Result<()>>,
+}
+
+impl Processor {
+    #[tracing::instrument(name = "Initializing Gui Processor", skip(config), err)]
+    pub fn new(config: &crate::setting::Config) -> Result<Self> {
+        // preview keeps working when the port is taken
+        let (stream, notice) = match config
+            .stream
+            .enabled
+            .then(|| MjpegStream::start(&config.stream))
+        {
+            Some(Ok(stream)) => {
+                let notice = format!("Streaming on http://{}/", stream.addr());
+                (
+                    Some(Arc::new(stream)),
+                    Some((MessageSeverity::Info, notice)),
+                )
+            }
+            Some(Err(err)) => (
+                None,
+                Some((
+                    MessageSeverity::Warning,
+                    format!("Stream unavailable: {}", err),
+                )),
+            ),
+            None => (None, None),
+        };
+        Ok(Self {
+            status: Arc::new(RwLock::new(ProcStatus::NotInitialized)),
+            model: Arc::new(Mutex::new(Model::new(&config.model)?)),
+            source: Arc::new(RwLock::new(source::Source::default())),
+            frame: Arc::new(RwLock::new(frame::Frame::default())),
+            progress: Arc::new(RwLock::new(None)),
+            detection: Arc::new(RwLock::new(DetectionStats::default())),
+            recording: Arc::new(Mutex::new(None)),
+            notice: Arc::new(Mutex::new(notice)),
+            finishing: Vec::new(),
+            input: config.input.clone(),
+            camera: config.camera.clone(),
+            video: config.video.clone(),
+            stream,
+            worker: ResultWorker::new("proc_worker"),
+        })
+    }
+
+    pub fn register(&mut self, ctx: &eframe::egui::Context) -> Result<()> {
+        {
+            self.source
+                .write()
+                .map_err(Error::as_guard_error)?
+                .register(ctx);
+        }
+        {
+            self.frame
+                .write()
+                .map_err(Error::as_guard_error)?
+                .register(ctx)
+        }
+        Ok(())
+    }
+
+    pub fn get_status(&self) -> ProcStatus {
+        match self.status.read() {
+            Ok(s) => s.clone(),
+            Err(_) => ProcStatus::Idle,
+        }
+    }
+
+    pub fn set_status(&self, status: ProcStatus) -> Result<()> {
+        *self.status.write().map_err(Error::as_guard_error)? = status;
+        Ok(())
+    }
+
+    pub fn get_source_img(&self) -> eframe::egui::Image {
+        use eframe::egui;
+        let status = self.get_status();
+        match status {
+            ProcStatus::Processing => egui::Image::new(LOADING_GIF),
+            ProcStatus::Idle | ProcStatus::Previewing | ProcStatus::Running => {
+                let src_binding = { self.source.read().map_err(Error::as_guard_error) };
+                let Ok(src) = src_binding.as_deref() else {
+                    return egui::Image::new(PROFILE_ICON);
+                };
+                egui::Image::from_texture(egui::load::SizedTexture::from_handle(&src.texture))
+            }
+            _ => egui::Image::new(PROFILE_ICON),
+        }
+    }
+
+    pub fn get_frame(&self) -> Result<eframe::egui::TextureHandle> {
+        Ok(self
+            .frame
+            .read()
+            .inspect_err(|_| {
+                let _ = self.set_status(ProcStatus::Idle);
+            })
+            .map_err(Error::as_guard_error)?
+            .clone())
+    }
+
+    pub fn set_source_with_path(&mut self, path: std::path::PathBuf) -> Result<()> {
+        self.set_status(ProcStatus::Processing)?;
+        let (stataus, source, model) = (
+            Arc::clone(&self.status),
+            Arc::clone(&self.source),
+            Arc::clone(&self.model),
+        );
+
+        self.worker.send(move || {
+            let img = Image::from_path(path, None)?;
+            let (tensor, vec_tensor) = {
+                model
+                    .lock()
+                    .map_err(Error::as_guard_error)?
+                    .vectorize_tensor(img.into())?
+            };
+            {
+                source
+                    .write()
+                    .map_err(Error::as_guard_error)?
+                    .set_from_tensor(tensor, vec_tensor);
+            }
+            {
+                *stataus.write().map_err(Error::as_guard_error)? = ProcStatus::Idle;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn run_preview(&mut self) -> Result<()> {
+        use std::time::{Duration, Instant};
+        self.set_status(ProcStatus::Previewing)?;
+        let (status, frame, source, model, recording, detection) = (
+            Arc::clone(&self.status),
+            Arc::clone(&self.frame),
+            Arc::clone(&self.source),
+            Arc::clone(&self.model),
+            Arc::clone(&self.recording),
+            Arc::clone(&self.detection),
+        );
+
+        let (input, camera, stream) =
+            (self.input.clone(), self.camera.clone(), self.stream.clone());
+        let notice = Arc::clone(&self.notice);
+
+        self.worker.send(move || {
+            let mut frames = input.open(&camera)?;
+            if let Some((format, mismatches)) = frames.capture_format() {
+                *notice.lock().map_err(Error::as_guard_error)? =
+                    Some(capture_notice(format, mismatches));
+            }
+            {
+                let mut model = model.lock().map_err(Error::as_guard_error)?;
+                model.reset_tracking();
+                if let Some(fps) = frames.frame_rate() {
+                    model.set_frame_rate(fps);
+                }
+            }
+            loop {
+                {
+                    if *status.read().map_err(Error::as_guard_error)? != ProcStatus::Previewing {
+                        frame
+                            .write()
+                            .map_err(Error::as_guard_error)?
+                            .set(crate::image::Image::default(), Default::default());
+                        break;
+                    }
+                }
+                let start_inst = Instant::now();
+                let Some(source_frame) = frames.next_frame()? else {
+                    // finite source exhausted, last frame stays on screen
+                    *status.write().map_err(Error::as_guard_error)? = ProcStatus::Idle;
+                    break;
+                };
+
+                // Processing Starts
+                let src = { source.read().map_err(Error::as_guard_error)?.data.clone() };
+                let data = {
+                    let mut model = model.lock().map_err(Error::as_guard_error)?;
+                    let data = model.run(source_frame.tensor, src.into())?;
+                    *detection.write().map_err(Error::as_guard_error)? = *model.detection_stats();
+                    data
+                };
+                // Processing Ends
+
+                if let Some(stream) = &stream {
+                    stream.publish(&data);
+                }
+                if let Some(recording) = recording.lock().map_err(Error::as_guard_error)?.as_ref() {
+                    recording.push(&data);
+                }
+                {
+                    frame
+                        .write()
+                        .map_err(Error::as_guard_error)?
+                        .set(data, Default::default());
+                }
+
+                let duration_since = Instant::now().duration_since(start_inst);
+                if Duration::from_millis(FRAME_DELAY) > duration_since {
+                    std::thread::sleep(Duration::from_millis(FRAME_DELAY) - duration_since)
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn get_progress(&self) -> Option<VideoProgress> {
+        self.progress.read().ok().and_then(|p| *p)
+    }
+
+    /// Detection vs propagation counts of the running preview or video
+    pub fn get_detection_stats(&self) -> DetectionStats {
+        self.detection.read().map(|d| *d).unwrap_or_default()
+    }
+
+    pub fn run_video(&mut self, input: std::path::PathBuf) -> Result<()> {
+        self.set_status(ProcStatus::Running)?;
+        let (status, frame, source, model, progress) = (
+            Arc::clone(&self.status),
+            Arc::clone(&self.frame),
+            Arc::clone(&self.source),
+            Arc::clone(&self.model),
+            Arc::clone(&self.progress),
+        );
+        let job = VideoJob::new(input, self.video.clone());
+        let (stream, detection) = (self.stream.clone(), Arc::clone(&self.detection));
+
+        self.worker.send(move || {
+            {
+                *progress.write().map_err(Error::as_guard_error)? = None;
+            }
+            let src = { source.read().map_err(Error::as_guard_error)?.data.clone() }.into();
+            let result = {
+                let mut model = model.lock().map_err(Error::as_guard_error)?;
+                job.run(&mut model, &src, |output, current| {
+                    if let Some(stream) = &stream {
+                        stream.publish(output);
+                    }
+                    if let Ok(mut frame) = frame.write() {
+                        frame.set(output.clone(), Default::default());
+                    }
+                    if let Ok(mut progress) = progress.write() {
+                        *progress = Some(current);
+                    }
+                    if let Ok(mut detection) = detection.write() {
+                        *detection = current.detection;
+                    }
+                    // stop button sets idle
+                    matches!(status.read().as_deref(), Ok(ProcStatus::Running))
+                })
+            };
+            {
+                *status.write().map_err(Error::as_guard_error)? = ProcStatus::Idle;
+            }
+            result.map(|_| ())
+        })
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.set_status(ProcStatus::Idle)?;
+        self.stop_recording()
+    }
+
+    /// Records the preview into `path` until stopped, container & codec from the video config
+    pub fn start_recording(&mut self, path: std::path::PathBuf) -> Result<()> {
+        self.stop_recording()?;
+        let recording = record::Recording::start(path, &self.video, RECORD_FRAME_RATE)?;
+        *self.recording.lock().map_err(Error::as_guard_error)? = Some(recording);
+        Ok(())
+    }
+
+    /// Closes the file in the background, failures come through `register_recording`
+    pub fn stop_recording(&mut self) -> Result<()> {
+        let recording = { self.recording.lock().map_err(Error::as_guard_error)?.take() };
+        if let Some(mut recording) = recording {
+            recording.stop();
+            self.finishing.push(recording);
+        }
+        Ok(())
+    }
+
+    /// (elapsed, bytes written) of the running recording
+    pub fn get_recording(&self) -> Option<(std::time::Duration, u64)> {
+        self.recording
+            .lock()
+            .ok()?
+            .as_ref()
+            .map(|r| (r.elapsed(), r.bytes()))
+    }
+
+    pub fn recording_extension(&self) -> String {
+        self.video.container.trim_start_matches('.').to_string()
+    }
+
+    /// Reports stopped recordings that failed, true while some are still flushing
+    pub fn register_recording<F>(&mut self, mut f: F) -> bool
+    where
+        F: FnMut(Error),
+    {
+        self.finishing
+            .retain(|recording| match recording.try_finish() {
+                Some(Err(err)) => {
+                    f(err);
+                    false
+                }
+                Some(Ok(())) => false,
+                None => true,
+            });
+        !self.finishing.is_empty()
+    }
+
+    /// (address, watching clients) of the MJPEG stream when it's up
+    pub fn get_stream(&self) -> Option<(std::net::SocketAddr, usize)> {
+        self.stream.as_ref().map(|s| (s.addr(), s.clients()))
+    }
+
+    /// Hands over the stream state at startup & the camera format of a preview that just started
+    pub fn register_notice<F>(&mut self, f: F)
+    where
+        F: FnOnce(MessageSeverity, String),
+    {
+        if let Some((severity, notice)) = self.notice.lock().ok().and_then(|mut n| n.take()) {
+            f(severity, notice);
+        }
+    }
+
+    pub fn register_error<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(Error),
+    {
+        let responds = match self.worker.try_recv() {
+            Ok(res) => res,
+            Err(err) => {
+                if std::sync::mpsc::TryRecvError::Empty == err {
+                    return Ok(());
+                }
+                self.set_status(ProcStatus::Idle)?;
+                f(Error::as_sync_error(err));
+                return Ok(());
+            }
+        };
+        if let Err(received_err) = responds {
+            self.set_status(ProcStatus::Idle)?;
+            f(received_err);
+        }
+        Ok(())
+    }
+}
+
+// a warning when the camera didn't agree to everything requested
+fn capture_notice(format: &CaptureFormat, mismatches: &[String]) -> (MessageSeverity, String) {
+    match mismatches {
+        [] => (
+            MessageSeverity::Info,
+            format!("Camera capturing {}", format),
+        ),
+        _ => (
+            MessageSeverity::Warning,
+            format!(
+                "Camera refused requested {}, capturing {}",
+                mismatches.join(", "),
+                format
+            ),
+        ),
+    }
+}
+
+impl Drop for Processor {
+    fn drop(&mut self) {
+        let _ = self.set_status(ProcStatus::Idle);
+    }
+}
@@ -0,0 +1,116 @@
+// Preview frames written to a video file off the preview loop
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use opencv::prelude::*;
+
+use crate::{
+    cv::Matrix,
+    job::{video::open_writer, VideoConfig},
+    model::Tensor,
+    sync::ResultWorker,
+    Error, Result,
+};
+
+// frames waiting on the writer, further ones are dropped & covered by repeats
+const QUEUE_SIZE: usize = 8;
+
+pub struct Recording {
+    started: Instant,
+    bytes: Arc<AtomicU64>,
+    // dropped before the worker so its loop ends
+    sender: Option<mpsc::SyncSender<(Tensor, Instant)>>,
+    worker: ResultWorker<Result<()>>,
+}
+
+impl Recording {
+    /// Written at a fixed `fps`, frames are repeated by wall clock so playback keeps real time
+    pub fn start(path: PathBuf, config: &VideoConfig, fps: f32) -> Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel::<(Tensor, Instant)>(QUEUE_SIZE);
+        let (started, bytes) = (Instant::now(), Arc::new(AtomicU64::new(0)));
+        let worker = ResultWorker::new("record_worker");
+
+        let (file, codec, written_bytes) = (path, config.codec.clone(), Arc::clone(&bytes));
+        worker.send(move || {
+            let mut writer = None;
+            let (mut size, mut written) = ((0, 0), 0);
+            for (frame, at) in receiver {
+                let (_, _, h, w) = frame.dim();
+                let writer = match writer.as_mut() {
+                    Some(_) if size != (w, h) => {
+                        tracing::warn!("Skipping {}x{} frame, recording is {:?}", w, h, size);
+                        continue;
+                    }
+                    Some(writer) => writer,
+                    None => {
+                        size = (w, h);
+                        writer.insert(open_writer(&file, &codec, fps, size)?)
+                    }
+                };
+
+                let matrix = Matrix::from_tensor(&frame)?;
+                let due = (at.duration_since(started).as_secs_f32() * fps) as usize + 1;
+                while written < due {
+                    writer.write(&*matrix).map_err(Error::CVError)?;
+                    written += 1;
+                }
+                if let Ok(meta) = std::fs::metadata(&file) {
+                    written_bytes.store(meta.len(), Ordering::Relaxed);
+                }
+            }
+
+            if let Some(mut writer) = writer {
+                writer.release().map_err(Error::CVError)?;
+            }
+            if let Ok(meta) = std::fs::metadata(&file) {
+                written_bytes.store(meta.len(), Ordering::Relaxed);
+            }
+            tracing::info!("Recorded {} frames to {}", written, file.display());
+            Ok(())
+        })?;
+
+        Ok(Self {
+            started,
+            bytes,
+            sender: Some(sender),
+            worker,
+        })
+    }
+
+    /// Never waits on the writer, frames are dropped while it's behind
+    pub fn push(&self, frame: &Tensor) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.try_send((frame.clone(), Instant::now()));
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Size of the file on disk so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Stops taking frames, the writer flushes the queue & closes the file on its own
+    pub fn stop(&mut self) {
+        self.sender.take();
+    }
+
+    /// Result of the writer once it's done, `None` while still flushing
+    pub fn try_finish(&self) -> Option<Result<()>> {
+        match self.worker.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(err) => Some(Err(Error::as_sync_error(err))),
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// EXIF orientation, phones store the sensor's pixels & a tag saying how to turn them
+// https://www.cipa.jp/std/documents/e/DC-X008-Translation-2019-E.pdf (4.6.4 A, Orientation)
+// https://magnushoff.com/articles/jpeg-orientation/
+
+const ORIENTATION_TAG: u16 = 0x0112;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// Orientation tag (1 ~ 8) of JPEG, PNG, WebP & TIFF data, None when missing or unreadable
+pub fn exif_orientation(bytes: &[u8]) -> Option<u8> {
+    let tiff = match bytes {
+        [0xff, 0xd8, ..] => jpeg_exif(bytes)?,
+        [0x89, b'P', b'N', b'G', ..] => png_exif(bytes)?,
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => webp_exif(bytes)?,
+        [b'I', b'I', 42, 0, ..] | [b'M', b'M', 0, 42, ..] => bytes,
+        _ => return None,
+    };
+    tiff_orientation(tiff.strip_prefix(EXIF_HEADER).unwrap_or(tiff))
+}
+
+/// Turns the decoded pixels upright
+pub fn apply(image: image::DynamicImage, orientation: u8) -> image::DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        // transpose
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        // transverse
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+// APP1 segment, markers before the scan data
+fn jpeg_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut i = 2;
+    while i + 4 <= bytes.len() && bytes[i] == 0xff {
+        let marker = bytes[i + 1];
+        // start of scan | end of image
+        if marker == 0xda || marker == 0xd9 {
+            return None;
+        }
+        let len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        let segment = bytes.get(i + 4..i + 2 + len)?;
+        if marker == 0xe1 && segment.starts_with(EXIF_HEADER) {
+            return Some(segment);
+        }
+        i += 2 + len;
+    }
+    None
+}
+
+// eXIf chunk
+fn png_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut i = 8;
+    while i + 8 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[i..i + 4].try_into().ok()?) as usize;
+        let data = bytes.get(i + 8..i + 8 + len)?;
+        if &bytes[i + 4..i + 8] == b"eXIf" {
+            return Some(data);
+        }
+        // length, type, data & crc
+        i += 12 + len;
+    }
+    None
+}
+
+// EXIF chunk of the RIFF container, padded to even sizes
+fn webp_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut i = 12;
+    while i + 8 <= bytes.len() {
+        let len = u32::from_le_bytes(bytes[i + 4..i + 8].try_into().ok()?) as usize;
+        let data = bytes.get(i + 8..i + 8 + len)?;
+        if &bytes[i..i + 4] == b"EXIF" {
+            return Some(data);
+        }
+        i += 8 + len + len % 2;
+    }
+    None
+}
+
+// first IFD entries, (tag, type, count, value)
+fn tiff_orientation(tiff: &[u8]) -> Option<u8> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| {
+        let b: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(match big_endian {
+            true => u16::from_be_bytes(b),
+            false => u16::from_le_bytes(b),
+        })
+    };
+    let u32_at = |at: usize| {
+        let b: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(match big_endian {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    (0..u16_at(ifd)? as usize)
+        .map(|n| ifd + 2 + n * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .and_then(|value| u8::try_from(value).ok())
+        .filter(|value| (1..=8).contains(value))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{apply, exif_orientation};
+
+    /// APP1 segment holding only an orientation tag
+    pub(crate) fn exif_segment(orientation: u16, big_endian: bool) -> Vec<u8> {
+        let u16_bytes = |v: u16| match big_endian {
+            true => v.to_be_bytes(),
+            false => v.to_le_bytes(),
+        };
+        let u32_bytes = |v: u32| match big_endian {
+            true => v.to_be_bytes(),
+            false => v.to_le_bytes(),
+        };
+        let mut tiff = match big_endian {
+            true => b"MM".to_vec(),
+            false => b"II".to_vec(),
+        };
+        tiff.extend(u16_bytes(42));
+        tiff.extend(u32_bytes(8));
+        tiff.extend(u16_bytes(1));
+        // orientation, SHORT, 1 value
+        tiff.extend(u16_bytes(0x0112));
+        tiff.extend(u16_bytes(3));
+        tiff.extend(u32_bytes(1));
+        tiff.extend(u16_bytes(orientation));
+        tiff.extend([0, 0]);
+        tiff.extend(u32_bytes(0));
+
+        let mut segment = vec![0xff, 0xe1];
+        segment.extend(((tiff.len() + 8) as u16).to_be_bytes());
+        segment.extend(b"Exif\0\0");
+        segment.extend(tiff);
+        segment
+    }
+
+    #[test]
+    fn reads_orientation_of_jpeg_and_png() {
+        for big_endian in [true, false] {
+            let mut jpeg = vec![0xff, 0xd8];
+            // unrelated APP0 first
+            jpeg.extend([0xff, 0xe0, 0, 4, 0, 0]);
+            jpeg.extend(exif_segment(6, big_endian));
+            jpeg.extend([0xff, 0xd9]);
+            assert_eq!(
+                exif_orientation(&jpeg),
+                Some(6),
+                "big endian {}",
+                big_endian
+            );
+        }
+
+        let tiff = exif_segment(8, false)[10..].to_vec();
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend((tiff.len() as u32).to_be_bytes());
+        png.extend(b"eXIf");
+        png.extend(&tiff);
+        png.extend([0; 4]);
+        assert_eq!(exif_orientation(&png), Some(8));
+
+        assert_eq!(exif_orientation(&[0xff, 0xd8, 0xff, 0xd9]), None);
+        assert_eq!(exif_orientation(b"not an image"), None);
+    }
+
+    #[test]
+    fn turns_pixels_upright() {
+        // 2 x 1, red then blue
+        let mut img = image::RgbImage::new(2, 1);
+        img.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+        img.put_pixel(1, 0, image::Rgb([0, 0, 255]));
+        let img = image::DynamicImage::ImageRgb8(img);
+
+        let rotated = apply(img.clone(), 6).to_rgb8();
+        assert_eq!(rotated.dimensions(), (1, 2));
+        assert_eq!(rotated.get_pixel(0, 0).0, [255, 0, 0]);
+
+        let transposed = apply(img.clone(), 5).to_rgb8();
+        assert_eq!(transposed.dimensions(), (1, 2));
+        assert_eq!(transposed.get_pixel(0, 0).0, [255, 0, 0]);
+
+        let transversed = apply(img.clone(), 7).to_rgb8();
+        assert_eq!(transversed.get_pixel(0, 0).0, [0, 0, 255]);
+
+        assert_eq!(apply(img.clone(), 1), img);
+    }
+}
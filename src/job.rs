@@ -0,0 +1,11 @@
+// Headless processing of recorded inputs, shared by the gui & command line
+
+pub use audio::AudioConfig;
+pub use batch::{BatchConfig, BatchFailure, BatchJob, BatchSummary};
+pub use pipe::{PipeFormat, PipeJob};
+pub use video::{VideoConfig, VideoJob, VideoProgress};
+
+pub mod audio;
+pub mod batch;
+pub mod pipe;
+pub mod video;
@@ -0,0 +1,169 @@
+// Audio passthrough, VideoWriter only writes the picture
+// https://ffmpeg.org/ffmpeg.html#Advanced-options
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{Error, Result};
+
+// lines of ffmpeg stderr kept in errors
+const STDERR_TAIL: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Remux the input's audio into processed videos, needs ffmpeg
+    pub enabled: bool,
+    /// ffmpeg binary, looked up in PATH when not a path
+    pub ffmpeg: PathBuf,
+    /// `copy` keeps the original stream, `aac` | `libopus` re-encode for containers that can't hold it
+    pub codec: String,
+    /// Forced muxer (`-f`), from the output extension when unset
+    pub format: Option<String>,
+    /// Extra arguments placed before the output path
+    pub args: Vec<String>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ffmpeg: PathBuf::from("ffmpeg"),
+            codec: "copy".into(),
+            format: None,
+            args: vec![],
+        }
+    }
+}
+
+impl AudioConfig {
+    /// Replaces `processed` with a copy carrying the audio of `original`, inputs without audio are left silent
+    #[tracing::instrument(name = "Remuxing audio", skip(self), err)]
+    pub fn remux(&self, original: &Path, processed: &Path) -> Result<()> {
+        let muxed = temp_output(processed);
+        let output = Command::new(&self.ffmpeg)
+            .args(self.ffmpeg_args(original, processed, &muxed))
+            .output()
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => Error::FfmpegError(format!(
+                    "{} not found, install ffmpeg or set video.audio.ffmpeg in config",
+                    self.ffmpeg.display()
+                )),
+                _ => Error::FfmpegError(format!(
+                    "Failed to start {}: {}",
+                    self.ffmpeg.display(),
+                    err
+                )),
+            })?;
+
+        if !output.status.success() {
+            let _ = std::fs::remove_file(&muxed);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let lines = stderr.lines().collect::<Vec<&str>>();
+            return Err(Error::FfmpegError(format!(
+                "Exited with {}: {}",
+                output.status,
+                lines[lines.len().saturating_sub(STDERR_TAIL)..].join("\n")
+            )));
+        }
+
+        std::fs::rename(&muxed, processed).map_err(Error::as_unknown_error)
+    }
+
+    fn ffmpeg_args(&self, original: &Path, processed: &Path, output: &Path) -> Vec<OsString> {
+        let mut args: Vec<OsString> = ["-y", "-v", "error", "-i"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        args.push(processed.into());
+        args.push("-i".into());
+        args.push(original.into());
+        // picture from the processed file, audio (if any) from the original
+        for arg in [
+            "-map",
+            "0:v:0",
+            "-map",
+            "1:a?",
+            "-c:v",
+            "copy",
+            "-c:a",
+            &self.codec,
+            "-shortest",
+        ] {
+            args.push(arg.into());
+        }
+        if let Some(format) = &self.format {
+            args.push("-f".into());
+            args.push(format.into());
+        }
+        args.extend(self.args.iter().map(OsString::from));
+        args.push(output.into());
+        args
+    }
+}
+
+// `clip.mp4` -> `clip.remux.mp4`, keeps the extension ffmpeg picks the muxer from
+fn temp_output(processed: &Path) -> PathBuf {
+    let stem = processed
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match processed.extension() {
+        Some(ext) => processed.with_file_name(format!("{}.remux.{}", stem, ext.to_string_lossy())),
+        None => processed.with_file_name(format!("{}.remux", stem)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::AudioConfig;
+    use crate::Error;
+
+    #[test]
+    fn builds_remux_arguments() {
+        let config = AudioConfig {
+            codec: "aac".into(),
+            format: Some("matroska".into()),
+            args: vec!["-movflags".into(), "+faststart".into()],
+            ..Default::default()
+        };
+        let args = config
+            .ffmpeg_args(
+                Path::new("in.mov"),
+                Path::new("out.mkv"),
+                Path::new("out.remux.mkv"),
+            )
+            .into_iter()
+            .map(|a| a.to_string_lossy().to_string())
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        assert_eq!(
+            args,
+            "-y -v error -i out.mkv -i in.mov -map 0:v:0 -map 1:a? -c:v copy -c:a aac -shortest \
+             -f matroska -movflags +faststart out.remux.mkv"
+        );
+        assert_eq!(
+            super::temp_output(Path::new("dir/out.mkv")),
+            PathBuf::from("dir/out.remux.mkv")
+        );
+    }
+
+    #[test]
+    fn reports_missing_ffmpeg() {
+        let config = AudioConfig {
+            enabled: true,
+            ffmpeg: PathBuf::from("/nonexistent/noface/ffmpeg"),
+            ..Default::default()
+        };
+        match config.remux(Path::new("in.mp4"), Path::new("out.mp4")) {
+            Err(Error::FfmpegError(err)) => assert!(err.contains("not found"), "Got {}", err),
+            other => panic!("Expected missing ffmpeg error, got {:?}", other),
+        }
+    }
+}
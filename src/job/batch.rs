@@ -0,0 +1,367 @@
+// Still image directory in, processed images out
+// https://docs.rs/rayon/latest/rayon/struct.ThreadPoolBuilder.html
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    image::Image,
+    model::{data::VectorizedTensor, Model},
+    Error, Result,
+};
+
+const SUMMARY_FILE: &str = "noface_batch.json";
+const INDEX_PLACEHOLDER: &str = "{index}";
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Output file name, `{stem}` | `{ext}` | `{index}` are replaced, the extension picks the format.
+    /// `{index}` follows the input listing & can't be resumed, it needs `skip_existing` off
+    pub template: String,
+    /// Images decoded & encoded at once, all cores when unset. Swapping itself runs one at a time
+    pub parallelism: Option<usize>,
+    /// Leave images with an existing output alone, resumes interrupted runs
+    pub skip_existing: bool,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            template: "{stem}_noface.{ext}".into(),
+            parallelism: None,
+            skip_existing: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct BatchFailure {
+    pub input: PathBuf,
+    pub reason: String,
+}
+
+/// Written next to the outputs as `noface_batch.json`
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct BatchSummary {
+    pub processed: usize,
+    pub skipped: usize,
+    pub failed: Vec<BatchFailure>,
+}
+
+enum Outcome {
+    Processed,
+    Skipped,
+    Failed(BatchFailure),
+}
+
+pub struct BatchJob {
+    pub inputs: Vec<PathBuf>,
+    pub output: PathBuf,
+    config: BatchConfig,
+}
+
+impl BatchJob {
+    /// `input` is a directory, a single image or a file name glob such as `photos/*.jpg`
+    pub fn new(input: &Path, output: PathBuf, config: BatchConfig) -> Result<Self> {
+        // an added or removed file shifts every index after it, resuming would skip the wrong images
+        if config.skip_existing && config.template.contains(INDEX_PLACEHOLDER) {
+            return Err(Error::UnknownError(
+                format!(
+                    "Template {} uses {}, which can't be resumed, turn off batch.skip_existing",
+                    config.template, INDEX_PLACEHOLDER
+                )
+                .into(),
+            ));
+        }
+        let inputs = collect_inputs(input)?;
+        if inputs
+            .iter()
+            .filter_map(|path| path.parent())
+            .any(|dir| same_dir(dir, &output))
+        {
+            return Err(Error::UnknownError(
+                format!(
+                    "Output directory {} must differ from the input",
+                    output.display()
+                )
+                .into(),
+            ));
+        }
+        // parallel writes to one name would clobber each other, or skip the later inputs on resume
+        let mut rendered = HashMap::new();
+        for (index, input) in inputs.iter().enumerate() {
+            let name = render_template(&config.template, index, input);
+            if let Some(first) = rendered.insert(name.clone(), input) {
+                return Err(Error::UnknownError(
+                    format!(
+                        "Template {} names both {} and {} as {}",
+                        config.template,
+                        first.display(),
+                        input.display(),
+                        name
+                    )
+                    .into(),
+                ));
+            }
+        }
+        Ok(Self {
+            inputs,
+            output,
+            config,
+        })
+    }
+
+    pub fn output_path(&self, index: usize, input: &Path) -> PathBuf {
+        self.output
+            .join(render_template(&self.config.template, index, input))
+    }
+
+    /// Swaps `src` into every input, failed images are recorded in the summary instead of stopping the run
+    #[tracing::instrument(name = "Processing images", skip_all, fields(count = self.inputs.len(), output = ?self.output), err)]
+    pub fn run(&self, model: &Mutex<Model>, src: &VectorizedTensor) -> Result<BatchSummary> {
+        std::fs::create_dir_all(&self.output).map_err(Error::as_unknown_error)?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.config.parallelism.unwrap_or(0))
+            .build()
+            .map_err(Error::as_unknown_error)?;
+
+        let outcomes = pool.install(|| {
+            self.inputs
+                .par_iter()
+                .enumerate()
+                .map(|(index, input)| {
+                    let output = self.output_path(index, input);
+                    if self.config.skip_existing && output.exists() {
+                        return Outcome::Skipped;
+                    }
+                    match self.process(model, src, input, &output) {
+                        Ok(()) => Outcome::Processed,
+                        Err(err) => {
+                            tracing::warn!("Failed processing {}: {}", input.display(), err);
+                            Outcome::Failed(BatchFailure {
+                                input: input.clone(),
+                                reason: err.to_string(),
+                            })
+                        }
+                    }
+                })
+                .collect::<Vec<Outcome>>()
+        });
+
+        let mut summary = BatchSummary::default();
+        for outcome in outcomes {
+            match outcome {
+                Outcome::Processed => summary.processed += 1,
+                Outcome::Skipped => summary.skipped += 1,
+                Outcome::Failed(failure) => summary.failed.push(failure),
+            }
+        }
+
+        let file = std::fs::File::create(self.output.join(SUMMARY_FILE))
+            .map_err(Error::as_unknown_error)?;
+        serde_json::to_writer_pretty(file, &summary).map_err(Error::as_unknown_error)?;
+        tracing::info!(
+            "Processed {}, skipped {}, failed {}",
+            summary.processed,
+            summary.skipped,
+            summary.failed.len()
+        );
+        Ok(summary)
+    }
+
+    fn process(
+        &self,
+        model: &Mutex<Model>,
+        src: &VectorizedTensor,
+        input: &Path,
+        output: &Path,
+    ) -> Result<()> {
+        let format = image::ImageFormat::from_path(output).map_err(Error::ImageError)?;
+        let image = Image::from_path(input.to_path_buf(), None)?;
+        let result = {
+            let mut model = model.lock().map_err(Error::as_guard_error)?;
+            // stills are unrelated, nothing to track between them
+            model.reset_tracking();
+            model.run(image.into(), src.0.clone().into())?
+        };
+
+        // written aside first so an interrupted save isn't skipped on resume
+        let partial = output.with_file_name(format!(
+            ".{}.part",
+            output.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        Image::from(result)
+            .save_with_format(&partial, format)
+            .map_err(Error::ImageError)?;
+        std::fs::rename(&partial, output).map_err(Error::as_unknown_error)
+    }
+}
+
+fn collect_inputs(input: &Path) -> Result<Vec<PathBuf>> {
+    let name = input
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (dir, pattern) = if input.is_dir() {
+        (input.to_path_buf(), None)
+    } else if name.contains(['*', '?']) {
+        let dir = input.parent().filter(|p| !p.as_os_str().is_empty());
+        (dir.unwrap_or(Path::new(".")).to_path_buf(), Some(name))
+    } else if input.is_file() {
+        return Ok(vec![input.to_path_buf()]);
+    } else {
+        return Err(Error::UnknownError(
+            format!("No images found at {}", input.display()).into(),
+        ));
+    };
+
+    let mut inputs = std::fs::read_dir(&dir)
+        .map_err(Error::as_unknown_error)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| match &pattern {
+            Some(pattern) => path
+                .file_name()
+                .is_some_and(|name| matches_glob(pattern, &name.to_string_lossy())),
+            None => Image::is_supported(path),
+        })
+        .collect::<Vec<PathBuf>>();
+    inputs.sort();
+    Ok(inputs)
+}
+
+// `*` any run of characters, `?` a single one
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some((p, rest)) => name
+                .split_first()
+                .is_some_and(|(n, name)| (*p == '?' || p == n) && matches(rest, name)),
+        }
+    }
+    matches(
+        &pattern.chars().collect::<Vec<char>>(),
+        &name.chars().collect::<Vec<char>>(),
+    )
+}
+
+fn render_template(template: &str, index: usize, input: &Path) -> String {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = input
+        .extension()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "png".into());
+    template
+        .replace("{stem}", &stem)
+        .replace("{ext}", &ext)
+        .replace(INDEX_PLACEHOLDER, &index.to_string())
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::{collect_inputs, matches_glob, render_template, BatchConfig, BatchJob};
+    use crate::testing::TempDir;
+
+    #[test]
+    fn renders_naming_template() {
+        assert_eq!(
+            render_template("{stem}_noface.{ext}", 3, Path::new("in/IMG 01.JPG")),
+            "IMG 01_noface.JPG"
+        );
+        assert_eq!(
+            render_template("{index}.png", 12, Path::new("in/a.jpg")),
+            "12.png"
+        );
+        assert!(matches_glob("IMG_*.jp?g", "IMG_0001.jpeg"));
+        assert!(matches_glob("*", "a.png"));
+        assert!(!matches_glob("IMG_*.jpg", "IMG_0001.png"));
+        assert!(!matches_glob("?.jpg", "ab.jpg"));
+    }
+
+    #[test]
+    fn rejects_resuming_index_templates() {
+        let dir = TempDir::new("batch");
+        std::fs::write(dir.join("a.jpg"), []).expect("Failed writing temp file");
+        let config = |skip_existing| BatchConfig {
+            template: "{index}.png".into(),
+            skip_existing,
+            ..Default::default()
+        };
+
+        let resumed = BatchJob::new(&dir, dir.join("out"), config(true));
+        let overwritten = BatchJob::new(&dir, dir.join("out"), config(false));
+
+        assert!(
+            resumed.is_err(),
+            "{{index}} template accepted with skip_existing"
+        );
+        assert!(overwritten.is_ok());
+    }
+
+    #[test]
+    fn rejects_templates_naming_inputs_alike() {
+        let dir = TempDir::new("batch");
+        for name in ["a.jpg", "a.png"] {
+            std::fs::write(dir.join(name), []).expect("Failed writing temp file");
+        }
+        let job = |template: &str| {
+            BatchJob::new(
+                &dir,
+                dir.join("out"),
+                BatchConfig {
+                    template: template.into(),
+                    skip_existing: false,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let same_stem = job("{stem}.png");
+        let constant = job("out.png");
+        let distinct = job("{stem}.{ext}.png");
+
+        assert!(same_stem.is_err(), "a.jpg & a.png both written to a.png");
+        assert!(constant.is_err(), "Every input written to out.png");
+        assert!(distinct.is_ok());
+    }
+
+    #[test]
+    fn collects_inputs_from_directory_or_glob() {
+        let dir = TempDir::new("batch");
+        for name in ["b.png", "a.jpg", "c.JPEG", "notes.txt"] {
+            std::fs::write(dir.join(name), []).expect("Failed writing temp file");
+        }
+
+        let all = collect_inputs(&dir).expect("Failed collecting directory");
+        let jpg = collect_inputs(&dir.join("*.jpg")).expect("Failed collecting glob");
+
+        assert_eq!(
+            all,
+            ["a.jpg", "b.png", "c.JPEG"]
+                .into_iter()
+                .map(|name| dir.join(name))
+                .collect::<Vec<PathBuf>>()
+        );
+        assert_eq!(jpg, vec![dir.join("a.jpg")]);
+    }
+}
@@ -0,0 +1,449 @@
+// Uncompressed frames on stdin, processed frames of the same format on stdout
+// https://wiki.multimedia.cx/index.php/YUV4MPEG2
+// https://en.wikipedia.org/wiki/YCbCr#ITU-R_BT.601_conversion
+
+use std::io::{BufRead, Write};
+
+use crate::{
+    image::Image,
+    model::{data::VectorizedTensor, Model},
+    Error, Result,
+};
+
+use super::video::FALLBACK_FRAME_RATE;
+
+const Y4M_MAGIC: &str = "YUV4MPEG2";
+// larger sides are rejected before allocating a frame, 16K covers any real stream
+const MAX_FRAME_SIDE: usize = 16384;
+
+/// `y4m` | `rgb24:WxH` | `bgr24:WxH`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PipeFormat {
+    /// Header carries size, rate & chroma layout
+    Y4m,
+    Rgb24 {
+        width: usize,
+        height: usize,
+    },
+    Bgr24 {
+        width: usize,
+        height: usize,
+    },
+}
+
+impl std::str::FromStr for PipeFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::UnknownError(
+                format!(
+                    "Invalid pipe format {}, expected y4m | rgb24:WxH | bgr24:WxH",
+                    s
+                )
+                .into(),
+            )
+        };
+        if s.eq_ignore_ascii_case("y4m") {
+            return Ok(Self::Y4m);
+        }
+        let (kind, size) = s.split_once(':').ok_or_else(invalid)?;
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .filter(|(w, h)| (1..=MAX_FRAME_SIDE).contains(w) && (1..=MAX_FRAME_SIDE).contains(h))
+            .ok_or_else(invalid)?;
+        match kind.to_ascii_lowercase().as_str() {
+            "rgb24" => Ok(Self::Rgb24 { width, height }),
+            "bgr24" => Ok(Self::Bgr24 { width, height }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chroma {
+    C420,
+    C422,
+    C444,
+    Mono,
+}
+
+impl Chroma {
+    fn parse(tag: &str) -> Result<Self> {
+        match tag {
+            "420" | "420jpeg" | "420paldv" | "420mpeg2" => Ok(Self::C420),
+            "422" => Ok(Self::C422),
+            "444" => Ok(Self::C444),
+            "mono" => Ok(Self::Mono),
+            _ => Err(Error::UnknownError(
+                format!("Unsupported y4m colorspace C{}, only 8 bit is", tag).into(),
+            )),
+        }
+    }
+
+    // (horizontal, vertical) subsampling
+    fn subsampling(&self) -> (usize, usize) {
+        match self {
+            Self::C420 => (2, 2),
+            Self::C422 => (2, 1),
+            Self::C444 | Self::Mono => (1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Y4mHeader {
+    width: usize,
+    height: usize,
+    fps: Option<f32>,
+    chroma: Chroma,
+    full_range: bool,
+    // written back unchanged
+    line: String,
+}
+
+impl Y4mHeader {
+    fn parse(line: &str) -> Result<Self> {
+        let mut params = line.split_ascii_whitespace();
+        if params.next() != Some(Y4M_MAGIC) {
+            return Err(Error::UnknownError("Input is not a y4m stream".into()));
+        }
+
+        let mut header = Self {
+            width: 0,
+            height: 0,
+            fps: None,
+            chroma: Chroma::C420,
+            full_range: false,
+            line: line.to_string(),
+        };
+        for param in params {
+            // lossy decoding may leave multi-byte characters anywhere
+            let Some(tag) = param.chars().next() else {
+                continue;
+            };
+            let value = &param[tag.len_utf8()..];
+            match tag {
+                'W' => header.width = value.parse().unwrap_or_default(),
+                'H' => header.height = value.parse().unwrap_or_default(),
+                'F' => {
+                    header.fps = value
+                        .split_once(':')
+                        .and_then(|(n, d)| Some((n.parse::<f32>().ok()?, d.parse::<f32>().ok()?)))
+                        .filter(|(_, d)| *d > 0.)
+                        .map(|(n, d)| n / d)
+                }
+                'C' => header.chroma = Chroma::parse(value)?,
+                'X' => header.full_range |= value.eq_ignore_ascii_case("COLORRANGE=FULL"),
+                _ => {}
+            }
+        }
+
+        if header.width == 0 || header.height == 0 {
+            return Err(Error::UnknownError(
+                format!("y4m header without frame size: {}", line).into(),
+            ));
+        }
+        if header.width > MAX_FRAME_SIDE || header.height > MAX_FRAME_SIDE {
+            return Err(Error::UnknownError(
+                format!(
+                    "y4m frame {}x{} exceeds {} per side",
+                    header.width, header.height, MAX_FRAME_SIDE
+                )
+                .into(),
+            ));
+        }
+        Ok(header)
+    }
+
+    fn chroma_size(&self) -> (usize, usize) {
+        let (sx, sy) = self.chroma.subsampling();
+        (self.width.div_ceil(sx), self.height.div_ceil(sy))
+    }
+
+    fn frame_len(&self) -> usize {
+        let (cw, ch) = self.chroma_size();
+        match self.chroma {
+            Chroma::Mono => self.width * self.height,
+            _ => self.width * self.height + 2 * cw * ch,
+        }
+    }
+
+    fn decode(&self, frame: &[u8]) -> image::RgbImage {
+        let (cw, ch) = self.chroma_size();
+        let (sx, sy) = self.chroma.subsampling();
+        let (luma, chroma) = frame.split_at(self.width * self.height);
+        image::RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let (x, y) = (x as usize, y as usize);
+            let (cb, cr) = match self.chroma {
+                Chroma::Mono => (128, 128),
+                _ => {
+                    let i = x / sx + y / sy * cw;
+                    (chroma[i], chroma[cw * ch + i])
+                }
+            };
+            image::Rgb(ycbcr_to_rgb(
+                luma[x + y * self.width],
+                cb,
+                cr,
+                self.full_range,
+            ))
+        })
+    }
+
+    fn encode(&self, image: &image::RgbImage) -> Vec<u8> {
+        let (cw, ch) = self.chroma_size();
+        let (sx, sy) = self.chroma.subsampling();
+        let mut frame = vec![128; self.frame_len()];
+        let mut sums = vec![(0., 0., 0); cw * ch];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let (x, y) = (x as usize, y as usize);
+            let (luma, cb, cr) = rgb_to_ycbcr(pixel.0, self.full_range);
+            frame[x + y * self.width] = luma;
+            let sum = &mut sums[x / sx + y / sy * cw];
+            *sum = (sum.0 + cb, sum.1 + cr, sum.2 + 1);
+        }
+        if self.chroma != Chroma::Mono {
+            let (cb, cr) = frame[self.width * self.height..].split_at_mut(cw * ch);
+            for (i, (sum_cb, sum_cr, n)) in sums.into_iter().enumerate() {
+                cb[i] = (sum_cb / n as f32).round().clamp(0., 255.) as u8;
+                cr[i] = (sum_cr / n as f32).round().clamp(0., 255.) as u8;
+            }
+        }
+        frame
+    }
+}
+
+// BT.601, limited range unless the stream says otherwise
+fn ycbcr_to_rgb(luma: u8, cb: u8, cr: u8, full_range: bool) -> [u8; 3] {
+    let (luma, cb, cr) = match full_range {
+        true => (luma as f32, cb as f32 - 128., cr as f32 - 128.),
+        false => (
+            (luma as f32 - 16.) * 255. / 219.,
+            (cb as f32 - 128.) * 255. / 224.,
+            (cr as f32 - 128.) * 255. / 224.,
+        ),
+    };
+    [
+        luma + 1.402 * cr,
+        luma - 0.344136 * cb - 0.714136 * cr,
+        luma + 1.772 * cb,
+    ]
+    .map(|v| v.round().clamp(0., 255.) as u8)
+}
+
+// chroma is kept unrounded for averaging
+fn rgb_to_ycbcr([r, g, b]: [u8; 3], full_range: bool) -> (u8, f32, f32) {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+    let cb = -0.168736 * r - 0.331264 * g + 0.5 * b;
+    let cr = 0.5 * r - 0.418688 * g - 0.081312 * b;
+    let (luma, cb, cr) = match full_range {
+        true => (luma, cb, cr),
+        false => (luma * 219. / 255. + 16., cb * 224. / 255., cr * 224. / 255.),
+    };
+    (luma.round().clamp(0., 255.) as u8, cb + 128., cr + 128.)
+}
+
+// false on a clean end of stream between frames
+fn read_frame(reader: &mut impl BufRead, buf: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => {
+                return Err(Error::UnknownError(
+                    format!("Truncated frame, got {} of {} bytes", filled, buf.len()).into(),
+                ))
+            }
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(Error::as_unknown_error(err)),
+        }
+    }
+    Ok(true)
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>> {
+    let mut line = vec![];
+    match reader
+        .read_until(b'\n', &mut line)
+        .map_err(Error::as_unknown_error)?
+    {
+        0 => Ok(None),
+        _ => Ok(Some(String::from_utf8_lossy(&line).trim_end().to_string())),
+    }
+}
+
+pub struct PipeJob {
+    pub format: PipeFormat,
+    /// Rate of raw input, y4m streams carry their own
+    pub fps: Option<f32>,
+}
+
+impl PipeJob {
+    pub fn new(format: PipeFormat) -> Self {
+        Self { format, fps: None }
+    }
+
+    pub fn with_fps(mut self, fps: f32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+
+    /// Processes frames until `reader` ends, flushing each one to `writer` as it's done
+    #[tracing::instrument(name = "Processing pipe", skip_all, fields(format = ?self.format), err)]
+    pub fn run(
+        &self,
+        model: &mut Model,
+        src: &VectorizedTensor,
+        mut reader: impl BufRead,
+        mut writer: impl Write,
+    ) -> Result<usize> {
+        let header = match self.format {
+            PipeFormat::Y4m => {
+                let line = read_line(&mut reader)?
+                    .ok_or_else(|| Error::UnknownError("Empty y4m stream".into()))?;
+                let header = Y4mHeader::parse(&line)?;
+                writeln!(writer, "{}", header.line).map_err(Error::as_unknown_error)?;
+                Some(header)
+            }
+            _ => None,
+        };
+        let fps = header
+            .as_ref()
+            .and_then(|h| h.fps)
+            .or(self.fps)
+            .unwrap_or(FALLBACK_FRAME_RATE);
+        model.reset_tracking();
+        model.set_frame_rate(fps);
+
+        let mut frames = 0;
+        loop {
+            let image = match (&header, self.format) {
+                (Some(header), _) => {
+                    // FRAME with optional per frame params
+                    match read_line(&mut reader)? {
+                        Some(line) if line.starts_with("FRAME") => {}
+                        Some(line) => {
+                            return Err(Error::UnknownError(
+                                format!("Expected y4m FRAME, got {}", line).into(),
+                            ))
+                        }
+                        None => break,
+                    }
+                    let mut buf = vec![0; header.frame_len()];
+                    if !read_frame(&mut reader, &mut buf)? {
+                        return Err(Error::UnknownError("y4m stream ends mid frame".into()));
+                    }
+                    header.decode(&buf)
+                }
+                (
+                    None,
+                    PipeFormat::Rgb24 { width, height } | PipeFormat::Bgr24 { width, height },
+                ) => {
+                    let mut buf = vec![0; width * height * 3];
+                    if !read_frame(&mut reader, &mut buf)? {
+                        break;
+                    }
+                    if matches!(self.format, PipeFormat::Bgr24 { .. }) {
+                        buf.chunks_exact_mut(3).for_each(|p| p.swap(0, 2));
+                    }
+                    image::RgbImage::from_raw(width as u32, height as u32, buf)
+                        .expect("Buffer sized to the frame")
+                }
+                (None, PipeFormat::Y4m) => unreachable!("y4m always has a header"),
+            };
+
+            let output = Image::from(model.run(Image::from(image).into(), src.0.clone().into())?);
+            let bytes = match (&header, self.format) {
+                (Some(header), _) => {
+                    writer
+                        .write_all(b"FRAME\n")
+                        .map_err(Error::as_unknown_error)?;
+                    header.encode(&output)
+                }
+                (None, PipeFormat::Bgr24 { .. }) => {
+                    let mut bytes = output.0.into_raw();
+                    bytes.chunks_exact_mut(3).for_each(|p| p.swap(0, 2));
+                    bytes
+                }
+                (None, _) => output.0.into_raw(),
+            };
+            writer.write_all(&bytes).map_err(Error::as_unknown_error)?;
+            writer.flush().map_err(Error::as_unknown_error)?;
+            frames += 1;
+        }
+
+        tracing::info!("Piped {} frames", frames);
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Chroma, PipeFormat, Y4mHeader};
+
+    #[test]
+    fn parses_pipe_formats_and_y4m_header() {
+        assert_eq!("y4m".parse::<PipeFormat>().ok(), Some(PipeFormat::Y4m));
+        assert_eq!(
+            "bgr24:640x360".parse::<PipeFormat>().ok(),
+            Some(PipeFormat::Bgr24 {
+                width: 640,
+                height: 360
+            })
+        );
+        assert!("rgb24:640".parse::<PipeFormat>().is_err());
+
+        let header = Y4mHeader::parse("YUV4MPEG2 W5 H3 F30000:1001 Ip A1:1 C422 XYSCSS=422")
+            .expect("Failed parsing header");
+        assert_eq!((header.width, header.height), (5, 3));
+        assert_eq!(header.chroma, Chroma::C422);
+        assert!((header.fps.unwrap_or_default() - 29.97).abs() < 0.01);
+        // odd width rounds the chroma planes up
+        assert_eq!(header.frame_len(), 5 * 3 + 2 * 3 * 3);
+        assert!(Y4mHeader::parse("YUV4MPEG2 W5 H3 C420p10").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_y4m_headers() {
+        // replacement characters from lossy decoding are skipped, not split inside
+        let header = Y4mHeader::parse("YUV4MPEG2 W4 H2 \u{FFFD}\u{FFFD} \u{e9}30 C444")
+            .expect("Failed parsing header with non ascii params");
+        assert_eq!((header.width, header.height), (4, 2));
+        assert_eq!(header.chroma, Chroma::C444);
+
+        assert!(Y4mHeader::parse("YUV4MPEG2 W4000000000 H4000000000").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W\u{FFFD} H4").is_err());
+        assert!("rgb24:100000x100000".parse::<PipeFormat>().is_err());
+    }
+
+    #[test]
+    fn round_trips_yuv_frames() {
+        for chroma in ["420jpeg", "444", "mono"] {
+            let header = Y4mHeader::parse(&format!("YUV4MPEG2 W4 H4 C{}", chroma))
+                .expect("Failed parsing header");
+            let color = match header.chroma {
+                Chroma::Mono => [90, 90, 90],
+                _ => [200, 40, 120],
+            };
+            let image = image::RgbImage::from_pixel(4, 4, image::Rgb(color));
+
+            let frame = header.encode(&image);
+            assert_eq!(frame.len(), header.frame_len());
+            for pixel in header.decode(&frame).pixels() {
+                for (got, expected) in pixel.0.iter().zip(color) {
+                    assert!(
+                        got.abs_diff(expected) <= 2,
+                        "C{} decoded {:?}, expected {:?}",
+                        chroma,
+                        pixel.0,
+                        color
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+// Video file in, processed video file out
+// https://docs.opencv.org/4.x/dd/d9e/classcv_1_1VideoWriter.html
+
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use opencv::{core, prelude::*, videoio};
+
+use crate::{
+    cv::{
+        camera::encode_fourcc,
+        source::{FrameSource, VideoSource},
+        Matrix,
+    },
+    model::{data::VectorizedTensor, tracker::DetectionStats, Model, Tensor},
+    Error, Result,
+};
+
+use super::AudioConfig;
+
+// sources reporting no rate are written at
+pub(crate) const FALLBACK_FRAME_RATE: f32 = 30.;
+const OUTPUT_SUFFIX: &str = "_noface";
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct VideoConfig {
+    /// FOURCC of the output codec, `mp4v` | `avc1` | `MJPG` | `XVID`, must be supported by the container
+    pub codec: String,
+    /// Output file extension, picks the container, `mp4` | `mkv` | `avi`
+    pub container: String,
+    /// Audio copied over from the input after writing
+    pub audio: AudioConfig,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            codec: "mp4v".into(),
+            container: "mp4".into(),
+            audio: AudioConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct VideoProgress {
+    /// Frames written so far
+    pub frames: usize,
+    /// Source frame count, container estimate
+    pub frame_count: Option<usize>,
+    /// Processing speed
+    pub fps: f32,
+    /// Frames detected vs propagated, see `DetectionSchedule`
+    pub detection: DetectionStats,
+}
+
+pub struct VideoJob {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    config: VideoConfig,
+}
+
+impl VideoJob {
+    /// Output next to the input, `clip.mov` -> `clip_noface.mp4`
+    pub fn new(input: PathBuf, config: VideoConfig) -> Self {
+        Self {
+            output: default_output(&input, &config.container),
+            input,
+            config,
+        }
+    }
+
+    pub fn with_output(mut self, output: PathBuf) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Swaps every frame of the input, keeping its fps, resolution & frame count.
+    /// `on_frame` sees each written frame, returning false cancels the job
+    #[tracing::instrument(name = "Processing video", skip_all, fields(input = ?self.input), err)]
+    pub fn run<F>(
+        &self,
+        model: &mut Model,
+        src: &VectorizedTensor,
+        mut on_frame: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(&Tensor, VideoProgress) -> bool,
+    {
+        let mut source = VideoSource::new(&self.input)?;
+        let fps = source.frame_rate().unwrap_or_else(|| {
+            tracing::warn!(
+                "Input has no frame rate, writing at {}fps",
+                FALLBACK_FRAME_RATE
+            );
+            FALLBACK_FRAME_RATE
+        });
+        model.reset_tracking();
+        model.set_frame_rate(fps);
+
+        let start = Instant::now();
+        let mut writer: Option<videoio::VideoWriter> = None;
+        let mut progress = VideoProgress {
+            frame_count: source.frame_count(),
+            ..Default::default()
+        };
+
+        while let Some(frame) = source.next_frame()? {
+            let (_, _, h, w) = frame.tensor.dim();
+            let output = model.run(frame.tensor, src.0.clone().into())?;
+
+            let writer = match writer.as_mut() {
+                Some(writer) => writer,
+                None => writer.insert(open_writer(&self.output, &self.config.codec, fps, (w, h))?),
+            };
+            writer
+                .write(&*Matrix::from_tensor(&output)?)
+                .map_err(Error::CVError)?;
+
+            progress.frames += 1;
+            progress.fps = progress.frames as f32 / start.elapsed().as_secs_f32().max(f32::EPSILON);
+            progress.detection = *model.detection_stats();
+            if !on_frame(&output, progress) {
+                tracing::info!("Cancelled after {} frames", progress.frames);
+                break;
+            }
+        }
+
+        if let Some(mut writer) = writer {
+            writer.release().map_err(Error::CVError)?;
+        }
+        if self.config.audio.enabled && progress.frames > 0 {
+            self.config.audio.remux(&self.input, &self.output)?;
+        }
+        if let Some(count) = progress.frame_count.filter(|c| *c != progress.frames) {
+            tracing::warn!("Wrote {} frames, input reported {}", progress.frames, count);
+        }
+        tracing::info!(
+            "Wrote {} frames to {}",
+            progress.frames,
+            self.output.display()
+        );
+        Ok(progress.frames)
+    }
+}
+
+/// Writer at `path` for frames of `(w, h)`, errors when the codec & container don't go together
+pub(crate) fn open_writer(
+    path: &Path,
+    codec: &str,
+    fps: f32,
+    (w, h): (usize, usize),
+) -> Result<videoio::VideoWriter> {
+    let writer = videoio::VideoWriter::new(
+        &path.to_string_lossy(),
+        encode_fourcc(codec)?,
+        fps as f64,
+        core::Size::new(w as i32, h as i32),
+        true,
+    )
+    .map_err(Error::CVError)?;
+
+    if !writer.is_opened().map_err(Error::CVError)? {
+        return Err(Error::UnknownError(
+            format!(
+                "Unable to write {} with codec {}, check the codec is supported by the container",
+                path.display(),
+                codec
+            )
+            .into(),
+        ));
+    }
+    Ok(writer)
+}
+
+fn default_output(input: &Path, container: &str) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    input.with_file_name(format!(
+        "{}{}.{}",
+        stem,
+        OUTPUT_SUFFIX,
+        container.trim_start_matches('.')
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use super::default_output;
+
+    #[test]
+    fn names_output_next_to_input() {
+        assert_eq!(
+            default_output(Path::new("clips/take 1.mov"), "mp4"),
+            PathBuf::from("clips/take 1_noface.mp4")
+        );
+        assert_eq!(
+            default_output(Path::new("clip.mkv"), ".mkv"),
+            PathBuf::from("clip_noface.mkv")
+        );
+    }
+}
@@ -1,13 +1,18 @@
-pub mod cv;
-pub mod error;
-pub mod gui;
-pub mod image;
-pub mod math;
-pub mod model;
-pub mod result;
-pub mod setting;
-pub mod sync;
-pub mod tracing;
-
-pub use error::Error;
-pub use result::Result;
+pub mod cv;
+pub mod error;
+pub mod gui;
+pub mod image;
+pub mod job;
+pub mod math;
+pub mod model;
+pub mod result;
+pub mod setting;
+pub mod stream;
+pub mod sync;
+pub mod tracing;
+
+#[cfg(test)]
+mod testing;
+
+pub use error::Error;
+pub use result::Result;
@@ -1,19 +1,112 @@
+use std::{path::PathBuf, sync::Mutex};
+
 use noface::{
+    cv::{list_devices, CV},
     gui::Gui,
-    model::register_ort,
+    image::Image,
+    job::{BatchJob, PipeFormat, PipeJob},
+    model::{register_ort, Model},
     result::Result,
     setting::Setting,
     tracing::{get_subscriber, init_subscriber},
+    Error,
 };
 
+const USAGE: &str = "Usage: noface [list-cameras | batch <source face> <input dir | glob> <output dir> | pipe <source face> [y4m | rgb24:WxH | bgr24:WxH] [fps]]";
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    init_subscriber(get_subscriber("noface", "off", std::io::stdout))?;
+    let args = std::env::args().collect::<Vec<String>>();
+    let command = args.get(1).map(String::as_str);
+    // stdout carries the frames when piping
+    if command == Some("pipe") {
+        init_subscriber(get_subscriber("noface", "off", std::io::stderr))?;
+    } else {
+        init_subscriber(get_subscriber("noface", "off", std::io::stdout))?;
+    }
     // Get Setting
     let setting = Setting::get()?;
+    if command == Some("list-cameras") {
+        let camera = &setting.config.camera;
+        for info in list_devices(camera.backend) {
+            println!("{}\t{}", info.device, info.name);
+        }
+        // what the configured camera agrees to, tracing is off by default
+        match CV::new(camera) {
+            Ok(cv) => {
+                println!("{} captures {}", camera.device, cv.format());
+                for mismatch in cv.mismatches() {
+                    eprintln!("Warning: {} refused requested {}", camera.device, mismatch);
+                }
+            }
+            Err(err) => eprintln!("Warning: unable to open {}: {}", camera.device, err),
+        }
+        return Ok(());
+    }
     // Register Models
     register_ort(&setting.config.model)?;
-    // Gui Create and Run
-    let gui = Gui::new(setting);
-    gui.run()
+    match command {
+        Some("pipe") => run_pipe(&setting, &args[2..]),
+        Some("batch") => run_batch(&setting, &args[2..]),
+        Some(command) => Err(Error::UnknownError(
+            format!("Unknown command {}\n{}", command, USAGE).into(),
+        )),
+        // Gui Create and Run
+        None => Gui::new(setting).run(),
+    }
+}
+
+// noface batch <source face> <input dir | glob> <output dir>
+fn run_batch(setting: &Setting, args: &[String]) -> Result<()> {
+    let [source, input, output, ..] = args else {
+        return Err(Error::UnknownError(
+            "Usage: noface batch <source face> <input dir | glob> <output dir>".into(),
+        ));
+    };
+    let job = BatchJob::new(
+        input.as_ref(),
+        PathBuf::from(output),
+        setting.config.batch.clone(),
+    )?;
+    let mut model = Model::new(&setting.config.model)?;
+    let (_, src) = model.vectorize_tensor(Image::from_path(source.into(), None)?.into())?;
+
+    let summary = job.run(&Mutex::new(model), &src)?;
+    println!(
+        "processed {}, skipped {}, failed {}",
+        summary.processed,
+        summary.skipped,
+        summary.failed.len()
+    );
+    for failure in summary.failed {
+        println!("{}\t{}", failure.input.display(), failure.reason);
+    }
+    Ok(())
+}
+
+// noface pipe <source face> [y4m | rgb24:WxH | bgr24:WxH] [fps]
+fn run_pipe(setting: &Setting, args: &[String]) -> Result<()> {
+    let Some(source) = args.first() else {
+        return Err(Error::UnknownError(
+            "Usage: noface pipe <source face> [y4m | rgb24:WxH | bgr24:WxH] [fps]".into(),
+        ));
+    };
+    let format = match args.get(1) {
+        Some(format) => format.parse::<PipeFormat>()?,
+        None => PipeFormat::Y4m,
+    };
+    let mut job = PipeJob::new(format);
+    if let Some(fps) = args.get(2) {
+        job = job.with_fps(fps.parse().map_err(Error::as_unknown_error)?);
+    }
+    let mut model = Model::new(&setting.config.model)?;
+    let (_, src) = model.vectorize_tensor(Image::from_path(source.into(), None)?.into())?;
+
+    job.run(
+        &mut model,
+        &src,
+        std::io::stdin().lock(),
+        std::io::BufWriter::new(std::io::stdout().lock()),
+    )?;
+    Ok(())
 }
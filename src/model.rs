@@ -1,111 +1,300 @@
-use data::VectorizedTensor;
-use detection_model::DetectionModel;
-use swap_model::SwapModel;
-use vectorization_model::VectorizationModel;
-
-use crate::{Error, Result};
-pub use data::{RecgnData, Tensor, TensorData};
-
-mod detection_model;
-mod swap_model;
-mod vectorization_model;
-
-pub mod data;
-
-type InputSizeMatrix = ndarray::Array<(usize, usize, usize, usize), ndarray::Dim<[usize; 4]>>;
-
-pub type ArcCudaDevice = std::sync::Arc<cudarc::driver::CudaDevice>;
-
-// extend to use get face location + embed swap face
-// https://github.com/pykeio/ort/blob/main/examples/cudarc/src/main.rs
-// https://onnxruntime.ai/docs/install/
-pub struct Model {
-    detect: DetectionModel,
-    swap: SwapModel,
-    vec: VectorizationModel,
-    cuda: Option<ArcCudaDevice>,
-}
-
-impl Model {
-    //might want thread count etc from config
-    #[tracing::instrument(name = "Initializing Models", skip(config), err)]
-    pub fn new(config: &crate::setting::ModelConfig) -> Result<Self> {
-        let model_base_path = std::env::current_dir()
-            .map_err(Error::as_unknown_error)?
-            .join("models");
-
-        Ok(Self {
-            detect: DetectionModel::new(model_base_path.join("det_10g.onnx"))?,
-            swap: SwapModel::new(model_base_path.join("inswapper_128.onnx"))?,
-            vec: VectorizationModel::new(model_base_path.join("w600k_r50.onnx"))?,
-            cuda: config
-                .cuda
-                .then_some(cudarc::driver::CudaDevice::new(0).map_err(Error::CudaError)?),
-        })
-    }
-
-    pub fn run(&mut self, mut tar: Tensor, src: VectorizedTensor) -> Result<Tensor> {
-        let faces = self.detect.run(tar.clone(), self.cuda.as_ref())?;
-        if faces.is_empty() {
-            return Ok(tar);
-        }
-
-        let face = faces[0].crop(&tar, Some(1.));
-        let swapped_tar = self.swap.run(face, src, self.cuda.as_ref())?;
-
-        let (_, bbox) = faces[0].get_scaled_bbox(1.);
-
-        tar.transpose(
-            swapped_tar,
-            (
-                bbox.0 as usize,
-                bbox.1 as usize,
-                bbox.2 as usize,
-                bbox.3 as usize,
-            ),
-        )?;
-
-        Ok(tar)
-    }
-
-    pub fn vectorize_tensor(&mut self, data: Tensor) -> Result<(Tensor, VectorizedTensor)> {
-        let faces = self.detect.run(data.clone(), self.cuda.as_ref())?;
-
-        if faces.is_empty() {
-            return Err(Error::InvalidModelIOError("No Face detected".into()));
-        }
-
-        let face_tensor = faces[0].crop_aligned(&data, Some(1.));
-
-        let vec_tensor = self
-            .vec
-            .run(face_tensor.clone(), self.cuda.as_ref())?
-            .prep_for_swap(&self.swap.graph.output);
-
-        Ok((face_tensor, vec_tensor))
-    }
-}
-
-#[tracing::instrument(err)]
-pub fn register_ort(config: &crate::setting::ModelConfig) -> Result<()> {
-    let onnx_env = ort::init().with_name("noface_image_procesor");
-
-    let onnx_env = match config.cuda {
-        true => onnx_env.with_execution_providers([ort::CUDAExecutionProvider::default()
-            .build()
-            .error_on_failure()]),
-        false => onnx_env,
-    };
-
-    onnx_env.commit().map_err(Error::ModelError)?;
-    Ok(())
-}
-
-fn start_session_from_file(onnx_path: std::path::PathBuf) -> Result<ort::Session> {
-    ort::Session::builder()
-        .map_err(Error::ModelError)?
-        .with_intra_threads(4)
-        .map_err(Error::ModelError)?
-        .commit_from_file(onnx_path)
-        .map_err(Error::ModelError)
-}
+use data::{Annotate, AnnotationStyle, Face, PoseLimits, VectorizedTensor};
+use detection_model::DetectionModel;
+pub use detection_model::{DetectorConfig, DetectorLayout, Suppression};
+use swap_model::SwapModel;
+use tracker::{
+    propagation::{DetectionStats, FrameKind},
+    Propagator, Tracker,
+};
+pub use vectorization_model::RecognitionConfig;
+use vectorization_model::VectorizationModel;
+
+use crate::{Error, Result};
+pub use data::{RecgnData, Tensor, TensorData};
+
+mod detection_model;
+mod swap_model;
+mod vectorization_model;
+
+pub mod data;
+pub mod tracker;
+
+type InputSizeMatrix = ndarray::Array<(usize, usize, usize, usize), ndarray::Dim<[usize; 4]>>;
+
+pub type ArcCudaDevice = std::sync::Arc<cudarc::driver::CudaDevice>;
+
+// extend to use get face location + embed swap face
+// https://github.com/pykeio/ort/blob/main/examples/cudarc/src/main.rs
+// https://onnxruntime.ai/docs/install/
+pub struct Model {
+    detect: DetectionModel,
+    swap: SwapModel,
+    vec: VectorizationModel,
+    cuda: Option<ArcCudaDevice>,
+    annotation: Option<AnnotationStyle>,
+    pose_limits: Option<PoseLimits>,
+    tracker: Tracker,
+    propagator: Propagator,
+}
+
+impl Model {
+    //might want thread count etc from config
+    #[tracing::instrument(name = "Initializing Models", skip(config), err)]
+    pub fn new(config: &crate::setting::ModelConfig) -> Result<Self> {
+        let model_base_path = std::env::current_dir()
+            .map_err(Error::as_unknown_error)?
+            .join("models");
+
+        Ok(Self {
+            detect: DetectionModel::new(
+                model_base_path.join(&config.detector.file),
+                config.detect_input.clone(),
+                &config.detector,
+                config.suppression.clone(),
+            )?,
+            swap: SwapModel::new(
+                model_base_path.join("inswapper_128.onnx"),
+                config.swap_input.clone(),
+                config.swap_output.clone(),
+            )?,
+            vec: VectorizationModel::new(
+                model_base_path.join(&config.recognition.file),
+                config.recognition_input.clone(),
+                &config.recognition,
+            )?,
+            cuda: config
+                .cuda
+                .then_some(cudarc::driver::CudaDevice::new(0).map_err(Error::CudaError)?),
+            annotation: config.annotation.clone(),
+            pose_limits: config.pose_limits.clone(),
+            tracker: Tracker::new(config.tracker.clone()),
+            propagator: Propagator::new(config.detection.clone()),
+        })
+    }
+
+    pub fn run(&mut self, mut tar: Tensor, src: VectorizedTensor) -> Result<Tensor> {
+        let start = std::time::Instant::now();
+        let (detected, kind) = self.find_faces(&tar)?;
+        // (track id, smoothed face)
+        let faces = self.track(&tar, &detected, kind);
+        let result = match self.swap_faces(&mut tar, src, &faces) {
+            // degenerate detection, frame is passed through as is
+            Err(Error::TransformError(err)) => {
+                tracing::warn!("Skipping swap of frame: {}", err);
+                Ok(())
+            }
+            result => result,
+        };
+
+        self.propagator.record(kind, start.elapsed());
+        result.map(|_| tar)
+    }
+
+    /// Frames detected vs propagated & the resulting fps gain
+    pub fn detection_stats(&self) -> &DetectionStats {
+        self.propagator.stats()
+    }
+
+    /// Propagated, roi detected or full frame detected faces in frame coordinates
+    fn find_faces(&mut self, frame: &Tensor) -> Result<(Vec<Face>, FrameKind)> {
+        let propagated = self
+            .propagator
+            .propagate(frame, self.tracker.tracks())
+            .unwrap_or_else(|err| {
+                tracing::warn!("Failed to propagate faces: {}", err);
+                None
+            });
+        if let Some(faces) = propagated {
+            return Ok((faces, FrameKind::Propagated));
+        }
+
+        let (_, _, h, w) = frame.dim();
+        if let Some(regions) = self.propagator.roi_regions(self.tracker.tracks(), (w, h)) {
+            if let Some(faces) = self.detect_regions(frame, &regions)? {
+                return Ok((faces, FrameKind::RoiDetection));
+            }
+        }
+
+        Ok((
+            self.detect.run(frame.clone(), self.cuda.as_ref())?,
+            FrameKind::FullDetection,
+        ))
+    }
+
+    /// Detection on each crop, upscaled to the detector input. `None` when a crop lost its face
+    fn detect_regions(
+        &mut self,
+        frame: &Tensor,
+        regions: &[(usize, usize, usize, usize)],
+    ) -> Result<Option<Vec<Face>>> {
+        let mut faces: Vec<Face> = vec![];
+        for region in regions {
+            let found = self.detect.run(frame.roi(*region), self.cuda.as_ref())?;
+            if found.is_empty() {
+                tracing::debug!("Lost face in roi {:?}, detecting full frame", region);
+                return Ok(None);
+            }
+            faces.extend(
+                found
+                    .into_iter()
+                    .map(|f| f.offset(region.0 as f32, region.1 as f32)),
+            );
+        }
+
+        // overlapping crops see the same face
+        faces.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let mut merged: Vec<Face> = vec![];
+        for face in faces {
+            if merged.iter().all(|m| m.iou(&face) < 0.5) {
+                merged.push(face);
+            }
+        }
+        Ok(Some(merged))
+    }
+
+    fn swap_faces(
+        &mut self,
+        tar: &mut Tensor,
+        src: VectorizedTensor,
+        faces: &[(usize, Face)],
+    ) -> Result<()> {
+        if faces.is_empty() {
+            return Ok(());
+        }
+
+        let weight = self.pose_weight(tar, &faces[0].1);
+        if weight > 0. {
+            let face = faces[0].1.crop(tar, Some(1.));
+            let swapped_tar = self.swap.run(face, src, self.cuda.as_ref())?;
+
+            let (_, bbox) = faces[0].1.get_scaled_bbox(1.);
+
+            tar.blend(
+                swapped_tar,
+                (
+                    bbox.0 as usize,
+                    bbox.1 as usize,
+                    bbox.2 as usize,
+                    bbox.3 as usize,
+                ),
+                weight,
+            )?;
+        }
+
+        if let Some(style) = &self.annotation {
+            for (id, face) in faces {
+                tar.annotate_face(face, Some(*id), style)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 0 ~ 1 opacity of the swap from the head pose, unlimited when the pose can't be solved
+    fn pose_weight(&self, frame: &Tensor, face: &Face) -> f32 {
+        let Some(limits) = &self.pose_limits else {
+            return 1.;
+        };
+        let (_, _, h, w) = frame.dim();
+        match face.head_pose((w, h)) {
+            Ok(pose) => limits.weight(&pose),
+            Err(err) => {
+                tracing::debug!("Failed to estimate head pose: {}", err);
+                1.
+            }
+        }
+    }
+
+    /// Forget tracked faces, call when the frame source changes
+    pub fn reset_tracking(&mut self) {
+        self.tracker.reset();
+        self.propagator.reset();
+    }
+
+    /// Source frame rate, keeps smoothing independent of processing speed
+    pub fn set_frame_rate(&mut self, fps: f32) {
+        self.tracker.set_frame_rate(fps);
+    }
+
+    fn track(&mut self, frame: &Tensor, faces: &[Face], kind: FrameKind) -> Vec<(usize, Face)> {
+        // propagated faces are the tracks moved, identity needs no recognition pass
+        let ids = if self.tracker.config().use_embedding && kind != FrameKind::Propagated {
+            self.track_with_embeddings(frame, faces)
+        } else {
+            self.tracker.update(faces)
+        };
+
+        ids.into_iter()
+            .zip(faces)
+            .map(|(id, face)| {
+                let face = self.tracker.get(id).map_or(face, |t| &t.face);
+                (id, face.clone())
+            })
+            .collect()
+    }
+
+    fn track_with_embeddings(&mut self, frame: &Tensor, faces: &[Face]) -> Vec<usize> {
+        let embeddings = faces
+            .iter()
+            .map(|face| {
+                face.crop_aligned(frame, Some(1.))
+                    .and_then(|crop| self.vec.run(crop, self.cuda.as_ref()))
+                    .inspect_err(|err| tracing::warn!("Skipping embedding of face: {}", err))
+                    .ok()
+            })
+            .collect::<Vec<Option<VectorizedTensor>>>();
+        self.tracker.update_with_embeddings(
+            faces,
+            &embeddings.iter().map(Option::as_ref).collect::<Vec<_>>(),
+        )
+    }
+
+    pub fn vectorize_tensor(&mut self, data: Tensor) -> Result<(Tensor, VectorizedTensor)> {
+        let faces = self.detect.run(data.clone(), self.cuda.as_ref())?;
+
+        if faces.is_empty() {
+            return Err(Error::InvalidModelIOError("No Face detected".into()));
+        }
+
+        let face_tensor = faces[0].crop_aligned(&data, Some(1.))?;
+
+        let latent = self.swap.graph.output.nrows();
+        if self.vec.embedding_size() != latent {
+            return Err(Error::InvalidModelIOError(format!(
+                "Recognition embedding size {} doesn't match swap model latent {}",
+                self.vec.embedding_size(),
+                latent
+            )));
+        }
+        let vec_tensor = self
+            .vec
+            .run(face_tensor.clone(), self.cuda.as_ref())?
+            .prep_for_swap(&self.swap.graph.output);
+
+        Ok((face_tensor, vec_tensor))
+    }
+}
+
+#[tracing::instrument(err)]
+pub fn register_ort(config: &crate::setting::ModelConfig) -> Result<()> {
+    let onnx_env = ort::init().with_name("noface_image_procesor");
+
+    let onnx_env = match config.cuda {
+        true => onnx_env.with_execution_providers([ort::CUDAExecutionProvider::default()
+            .build()
+            .error_on_failure()]),
+        false => onnx_env,
+    };
+
+    onnx_env.commit().map_err(Error::ModelError)?;
+    Ok(())
+}
+
+fn start_session_from_file(onnx_path: std::path::PathBuf) -> Result<ort::Session> {
+    ort::Session::builder()
+        .map_err(Error::ModelError)?
+        .with_intra_threads(4)
+        .map_err(Error::ModelError)?
+        .commit_from_file(onnx_path)
+        .map_err(Error::ModelError)
+}
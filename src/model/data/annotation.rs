@@ -0,0 +1,436 @@
+// Debug overlay for detections, drawn straight onto frame pixels
+
+use nalgebra::Matrix3;
+
+use super::{BBox, ChannelOrder, Face, KeyPoints, Tensor};
+
+/// 0~255 pixel value
+pub type Rgb = [u8; 3];
+
+// arcface aligned crop size
+const ALIGNED_SIZE: f32 = 112.;
+const GLYPH_W: usize = 3;
+const GLYPH_H: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AnnotationStyle {
+    pub bbox_color: Rgb,
+    pub thickness: usize,
+    pub keypoint_color: Rgb,
+    pub keypoint_radius: usize,
+    pub text_color: Rgb,
+    /// Pixel size of a single glyph dot
+    pub text_scale: usize,
+    pub show_score: bool,
+    pub show_grid: bool,
+    pub grid_color: Rgb,
+    pub grid_divisions: usize,
+}
+
+impl Default for AnnotationStyle {
+    fn default() -> Self {
+        Self {
+            bbox_color: [127, 255, 0],
+            thickness: 2,
+            keypoint_color: [255, 0, 0],
+            keypoint_radius: 2,
+            text_color: [255, 255, 255],
+            text_scale: 2,
+            show_score: true,
+            show_grid: false,
+            grid_color: [0, 191, 255],
+            grid_divisions: 4,
+        }
+    }
+}
+
+/// Pixel sink the annotation layer draws on
+pub trait Canvas {
+    /// (w, h)
+    fn canvas_size(&self) -> (usize, usize);
+
+    fn put_pixel(&mut self, x: usize, y: usize, color: Rgb);
+}
+
+/// Drawing primitives, coordinates outside of the canvas are clipped
+pub trait Annotate: Canvas {
+    /// Inclusive (x1, y1, x2, y2)
+    fn fill_rect(&mut self, rect: (isize, isize, isize, isize), color: Rgb) {
+        let (w, h) = self.canvas_size();
+        if w == 0 || h == 0 {
+            return;
+        }
+        let (x1, x2) = (rect.0.max(0), rect.2.min(w as isize - 1));
+        let (y1, y2) = (rect.1.max(0), rect.3.min(h as isize - 1));
+        for y in y1..=y2 {
+            for x in x1..=x2 {
+                self.put_pixel(x as usize, y as usize, color);
+            }
+        }
+    }
+
+    fn draw_line(&mut self, from: [f32; 2], to: [f32; 2], color: Rgb, thickness: usize) {
+        if from.iter().chain(to.iter()).any(|v| !v.is_finite()) {
+            return;
+        }
+        let (lo, hi) = brush(thickness);
+        let (w, h) = self.canvas_size();
+        // only the part whose brush reaches the canvas (+1px for rounding) is stepped,
+        // off canvas ends can be arbitrarily far
+        let min = [-(hi as f64) - 1., -(hi as f64) - 1.];
+        let max = [(w as isize + lo) as f64, (h as isize + lo) as f64];
+        let Some((from, to)) = clip_segment(from, to, min, max) else {
+            return;
+        };
+        let steps = (to[0] - from[0]).abs().max((to[1] - from[1]).abs()).ceil() as usize;
+        for step in 0..=steps {
+            let t = if steps == 0 {
+                0.
+            } else {
+                step as f32 / steps as f32
+            };
+            let (x, y) = (
+                (from[0] + (to[0] - from[0]) * t).round() as isize,
+                (from[1] + (to[1] - from[1]) * t).round() as isize,
+            );
+            self.fill_rect((x - lo, y - lo, x + hi, y + hi), color);
+        }
+    }
+
+    fn draw_bbox(&mut self, bbox: BBox, color: Rgb, thickness: usize) {
+        let corners = [
+            [bbox.0, bbox.1],
+            [bbox.2, bbox.1],
+            [bbox.2, bbox.3],
+            [bbox.0, bbox.3],
+        ];
+        for idx in 0..corners.len() {
+            self.draw_line(
+                corners[idx],
+                corners[(idx + 1) % corners.len()],
+                color,
+                thickness,
+            );
+        }
+    }
+
+    fn draw_keypoints(&mut self, keypoints: &KeyPoints, color: Rgb, radius: usize) {
+        let radius = radius as isize;
+        for [x, y] in keypoints.iter() {
+            if !x.is_finite() || !y.is_finite() {
+                continue;
+            }
+            let (x, y) = (x.round() as isize, y.round() as isize);
+            self.fill_rect((x - radius, y - radius, x + radius, y + radius), color);
+        }
+    }
+
+    /// Top left anchored text, only digits and `.#-:` are rendered
+    fn draw_text(&mut self, pos: [f32; 2], text: &str, color: Rgb, scale: usize) {
+        let scale = scale.max(1) as isize;
+        let (x, y) = (pos[0].round() as isize, pos[1].round() as isize);
+        for (idx, ch) in text.chars().enumerate() {
+            let glyph_x = x + idx as isize * (GLYPH_W as isize + 1) * scale;
+            for (row, bits) in glyph(ch).iter().enumerate() {
+                for col in 0..GLYPH_W {
+                    if bits & (1 << (GLYPH_W - 1 - col)) == 0 {
+                        continue;
+                    }
+                    let (px, py) = (glyph_x + col as isize * scale, y + row as isize * scale);
+                    self.fill_rect((px, py, px + scale - 1, py + scale - 1), color);
+                }
+            }
+        }
+    }
+
+    /// Grid of the aligned crop projected back onto the frame,
+    /// `matrix` maps frame pixels into the `size` x `size` aligned crop (umeyama)
+    fn draw_alignment_grid(
+        &mut self,
+        matrix: &Matrix3<f32>,
+        size: f32,
+        divisions: usize,
+        color: Rgb,
+    ) -> crate::Result<()> {
+        let inverse = matrix.try_inverse().ok_or_else(|| {
+            crate::Error::TransformError(format!(
+                "Alignment matrix is not invertible: {:?}",
+                matrix.as_slice()
+            ))
+        })?;
+        let project = |x: f32, y: f32| {
+            let p = inverse * nalgebra::Matrix3x1::<f32>::new(x, y, 1.);
+            [p.x, p.y]
+        };
+
+        let divisions = divisions.max(1);
+        for idx in 0..=divisions {
+            let t = size * idx as f32 / divisions as f32;
+            self.draw_line(project(t, 0.), project(t, size), color, 1);
+            self.draw_line(project(0., t), project(size, t), color, 1);
+        }
+        Ok(())
+    }
+
+    /// BBox, keypoints & `#track score` label of a single detection
+    fn annotate_face(
+        &mut self,
+        face: &Face,
+        track_id: Option<usize>,
+        style: &AnnotationStyle,
+    ) -> crate::Result<()> {
+        if style.show_grid {
+            // a collapsed detection has no grid, the rest is still drawn
+            match face.keypoints.umeyama_to_arc(ALIGNED_SIZE as usize) {
+                Ok(matrix) => self.draw_alignment_grid(
+                    &matrix,
+                    ALIGNED_SIZE,
+                    style.grid_divisions,
+                    style.grid_color,
+                )?,
+                Err(err) => tracing::debug!("Skipping alignment grid: {}", err),
+            }
+        }
+        self.draw_bbox(face.bbox, style.bbox_color, style.thickness);
+        self.draw_keypoints(&face.keypoints, style.keypoint_color, style.keypoint_radius);
+
+        let label = [
+            track_id.map(|id| format!("#{}", id)),
+            style.show_score.then(|| format!("{:.2}", face.score)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>()
+        .join(" ");
+        if label.is_empty() {
+            return Ok(());
+        }
+
+        // above the box, or inside when it would leave the frame
+        let text_h = (GLYPH_H * style.text_scale.max(1) + style.thickness) as f32;
+        let y = if face.bbox.1 - text_h >= 0. {
+            face.bbox.1 - text_h
+        } else {
+            face.bbox.1 + style.thickness as f32
+        };
+        self.draw_text([face.bbox.0, y], &label, style.text_color, style.text_scale);
+        Ok(())
+    }
+
+    fn annotate_faces(&mut self, faces: &[Face], style: &AnnotationStyle) -> crate::Result<()> {
+        faces
+            .iter()
+            .try_for_each(|face| self.annotate_face(face, None, style))
+    }
+}
+
+impl<T: Canvas + ?Sized> Annotate for T {}
+
+impl Canvas for Tensor {
+    fn canvas_size(&self) -> (usize, usize) {
+        let (_, _, h, w) = self.dim();
+        (w, h)
+    }
+
+    /// Drawn on every batch, gray channels get the luma of `color`
+    fn put_pixel(&mut self, x: usize, y: usize, color: Rgb) {
+        let (batch, channels, _, _) = self.dim();
+        let [r, g, b] = color.map(|v| v as f32);
+        for c in 0..channels {
+            let pixel = match (channels, c, self.order) {
+                (1 | 2, 0, _) => 0.299 * r + 0.587 * g + 0.114 * b,
+                (1 | 2, _, _) | (_, 3.., _) => 255.,
+                (_, c, ChannelOrder::Rgb) => [r, g, b][c],
+                (_, c, ChannelOrder::Bgr) => [b, g, r][c],
+            };
+            let v = self.normal.from_pixel(c, pixel);
+            for n in 0..batch {
+                self[(n, c, y, x)] = v;
+            }
+        }
+    }
+}
+
+/// Liang–Barsky clip of the segment to the `min` ~ `max` box, `None` when it misses it
+fn clip_segment(
+    from: [f32; 2],
+    to: [f32; 2],
+    min: [f64; 2],
+    max: [f64; 2],
+) -> Option<([f32; 2], [f32; 2])> {
+    let (from, to) = (from.map(f64::from), to.map(f64::from));
+    let delta = [to[0] - from[0], to[1] - from[1]];
+    // (t, clipping edge as (axis, value))
+    let (mut start, mut end) = ((0f64, None), (1f64, None));
+    for axis in 0..2 {
+        for (p, q, edge) in [
+            (-delta[axis], from[axis] - min[axis], min[axis]),
+            (delta[axis], max[axis] - from[axis], max[axis]),
+        ] {
+            if p == 0. {
+                if q < 0. {
+                    return None;
+                }
+                continue;
+            }
+            let t = q / p;
+            if p < 0. && t > start.0 {
+                start = (t, Some((axis, edge)));
+            } else if p > 0. && t < end.0 {
+                end = (t, Some((axis, edge)));
+            }
+        }
+    }
+    if start.0 > end.0 {
+        return None;
+    }
+    // the clipped axis lands on the edge exactly, far apart ends lose precision when interpolated
+    let point = |(t, edge): (f64, Option<(usize, f64)>)| {
+        [0, 1].map(|axis| match edge {
+            Some((edge_axis, value)) if edge_axis == axis => value as f32,
+            _ => (from[axis] + delta[axis] * t).clamp(min[axis], max[axis]) as f32,
+        })
+    };
+    Some((point(start), point(end)))
+}
+
+/// Square brush extent around the center pixel
+fn brush(thickness: usize) -> (isize, isize) {
+    let thickness = thickness.max(1) as isize;
+    ((thickness - 1) / 2, thickness / 2)
+}
+
+/// 3 x 5 bitmap rows, msb on the left
+fn glyph(ch: char) -> [u8; GLYPH_H] {
+    match ch {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        _ => [0; GLYPH_H],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Annotate, AnnotationStyle};
+    use crate::model::{
+        data::{ChannelOrder, Face, KeyPoints, Normal},
+        Tensor, TensorData,
+    };
+
+    fn test_face() -> Face {
+        Face {
+            score: 0.87,
+            bbox: (10., 20., 40., 60.),
+            keypoints: KeyPoints([[18., 32.], [32., 32.], [25., 40.], [19., 50.], [31., 50.]]),
+        }
+    }
+
+    #[test]
+    fn draws_bbox_with_color_and_thickness() {
+        let mut tensor = Tensor::new(Normal::U8, TensorData::zeros((2, 3, 32, 32)))
+            .with_order(ChannelOrder::Bgr);
+        tensor.draw_bbox((4., 4., 20., 20.), [10, 20, 30], 3);
+
+        for n in 0..2 {
+            // thickness spreads both sides of the edge
+            for y in [3, 4, 5] {
+                assert_eq!(
+                    tensor.pixel_rgba(n, y, 12)[..3],
+                    [10, 20, 30],
+                    "Edge at y = {}",
+                    y
+                );
+            }
+            assert_eq!(
+                tensor.pixel_rgba(n, 2, 12)[..3],
+                [0, 0, 0],
+                "Outside of edge"
+            );
+            assert_eq!(
+                tensor.pixel_rgba(n, 12, 12)[..3],
+                [0, 0, 0],
+                "Inside of box"
+            );
+        }
+        // channel 0 of bgr tensor is blue
+        assert_eq!(tensor[(0, 0, 4, 12)], 30.);
+    }
+
+    #[test]
+    fn clips_annotations_outside_of_canvas() {
+        let mut tensor = Tensor::new(Normal::N1ToP1, TensorData::zeros((1, 1, 16, 16)));
+        let face = Face {
+            bbox: (-30., -30., 100., 100.),
+            ..test_face()
+        };
+        let style = AnnotationStyle {
+            show_grid: true,
+            ..Default::default()
+        };
+        tensor
+            .annotate_face(&face, Some(3), &style)
+            .expect("Failed to annotate face");
+    }
+
+    #[test]
+    fn clips_far_off_canvas_lines() {
+        let mut tensor = Tensor::new(Normal::U8, TensorData::zeros((1, 3, 16, 16)));
+        let start = std::time::Instant::now();
+        // would take ~1e30 steps unclipped
+        tensor.draw_line([-1e30, 8.], [1e30, 8.], [255, 255, 255], 1);
+        tensor.draw_line([4., -1e20], [4., 3e38], [255, 255, 255], 1);
+        tensor.draw_line([-1e30, -1e30], [-1e29, 1e30], [255, 0, 0], 1);
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        for x in 0..16 {
+            assert_eq!(
+                tensor.pixel_rgba(0, 8, x)[..3],
+                [255, 255, 255],
+                "Row at x = {}",
+                x
+            );
+            assert_eq!(
+                tensor.pixel_rgba(0, x, 4)[..3],
+                [255, 255, 255],
+                "Column at y = {}",
+                x
+            );
+        }
+        assert_eq!(
+            tensor.pixel_rgba(0, 0, 0)[..3],
+            [0, 0, 0],
+            "Line off canvas drawn"
+        );
+    }
+
+    #[test]
+    fn annotates_face_on_image() {
+        let mut image = crate::image::Image::from(image::RgbImage::new(64, 80));
+        let style = AnnotationStyle::default();
+        image
+            .annotate_face(&test_face(), Some(12), &style)
+            .expect("Failed to annotate face");
+
+        assert_eq!(image[(25, 20)].0, style.bbox_color, "BBox top edge");
+        assert_eq!(image[(25, 40)].0, style.keypoint_color, "Nose keypoint");
+        // label sits above the box
+        let label_pixels = (0..20)
+            .flat_map(|y| (0..64).map(move |x| (x, y)))
+            .filter(|(x, y)| image[(*x, *y)].0 == style.text_color)
+            .count();
+        assert!(label_pixels > 0, "Missing label above the box");
+    }
+}
@@ -1,122 +1,297 @@
-use nalgebra::Matrix3;
-
-use crate::math::Math;
-
-const KEY_POINTS_LEN: usize = 5;
-const ARC_FACE_DST: KeyPoints = KeyPoints([
-    [38.2946, 51.6963],
-    [73.5318, 51.5014],
-    [56.0252, 71.7366],
-    [41.5493, 92.3655],
-    [70.7299, 92.2041],
-]);
-
-#[derive(Debug, Clone)]
-pub struct KeyPoints(pub [[f32; 2]; KEY_POINTS_LEN]);
-
-impl KeyPoints {
-    fn mean(&self) -> [f32; 2] {
-        Math::mean(self.0)
-    }
-
-    fn scale(&self, ratio: f32) -> Self {
-        Self(self.0.map(|r| [r[0] * ratio, r[1] * ratio]))
-    }
-
-    pub fn umeyama(&self, dst: &Self) -> nalgebra::Matrix3<f32> {
-        use nalgebra::{ArrayStorage, Matrix, Matrix1x2, Matrix2, Matrix2x1};
-        use std::ops::Mul;
-        let [src_x_mean, src_y_mean] = self.mean();
-        let [dst_x_mean, dst_y_mean] = dst.mean();
-        let (src_dmean, dst_dmean) = (
-            Matrix::from_array_storage(ArrayStorage(
-                self.0.map(|[x, y]| [x - src_x_mean, y - src_y_mean]),
-            )),
-            Matrix::from_array_storage(ArrayStorage(
-                dst.0.map(|[x, y]| [x - dst_x_mean, y - dst_y_mean]),
-            )),
-        );
-        let a = std::ops::Mul::mul(dst_dmean, &src_dmean.transpose()) / 5.;
-        let svd = Matrix::svd(a, true, true);
-        let determinant = a.determinant();
-
-        let mut d = [1f32; 2];
-        if determinant < 0. {
-            d[1] = -1.;
-        }
-
-        let mut t = Matrix2::<f32>::identity();
-        let (s, u, v) = (svd.singular_values, svd.u.unwrap(), svd.v_t.unwrap());
-
-        let rank = a.rank(0.00001f32);
-        if rank == 0 {
-            panic!("Matrix rank is 0");
-        }
-
-        if rank == 1 {
-            if u.determinant() * v.determinant() > 0. {
-                u.mul_to(&v, &mut t);
-            } else {
-                let s = d[1];
-                d[1] = -1.;
-                let dg = Matrix2::<f32>::new(d[0], 0., 0., d[1]);
-
-                let udg = u.mul(&dg);
-                udg.mul_to(&v, &mut t);
-                d[1] = s;
-            }
-        } else {
-            let dg = Matrix2::<f32>::new(d[0], 0., 0., d[1]);
-            let udg = u.mul(&dg);
-            udg.mul_to(&v, &mut t);
-        }
-
-        let ddd = Matrix1x2::new(d[0], d[1]);
-        let d_x_s = ddd.mul(s);
-
-        let (var0, var1) = (
-            src_dmean.remove_row(0).variance(),
-            src_dmean.remove_row(1).variance(),
-        );
-
-        let var_sum = var0 + var1;
-
-        let scale = d_x_s.get((0, 0)).unwrap() / var_sum;
-
-        let (dst_mean, src_mean) = (
-            Matrix2x1::<f32>::new(dst_x_mean, dst_y_mean),
-            Matrix2x1::<f32>::new(src_x_mean, src_y_mean),
-        );
-        let t_x_src_mean = t.mul(&src_mean);
-
-        let xxx = scale * t_x_src_mean;
-        let yyy = dst_mean - xxx;
-
-        let (m13, m23) = (*yyy.get(0).unwrap(), *yyy.get(1).unwrap());
-
-        let m00x22 = t * scale;
-
-        let (m11, m21, m12, m22) = (m00x22.m11, m00x22.m21, m00x22.m12, m00x22.m22);
-
-        Matrix3::<f32>::new(m11, m12, m13, m21, m22, m23, 0., 0., 1.)
-    }
-
-    pub fn umeyama_to_arc(&self, max_dim: usize) -> Matrix3<f32> {
-        let ratio = max_dim as f32 / 112.;
-        self.umeyama(&ARC_FACE_DST.scale(ratio))
-    }
-}
-
-impl std::ops::Deref for KeyPoints {
-    type Target = [[f32; 2]; KEY_POINTS_LEN];
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl std::ops::DerefMut for KeyPoints {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
+use nalgebra::Matrix3;
+
+use crate::math::Math;
+
+const KEY_POINTS_LEN: usize = 5;
+// Tukey biweight tuning constant, 95% efficiency on gaussian noise
+const TUKEY_C: f32 = 4.685;
+const IRLS_ITERATIONS: usize = 5;
+// similarity transform is fully determined by 2 points, 3 leaves one to verify
+const MIN_INLIERS: usize = 3;
+const MIN_RESIDUAL_RATIO: f32 = 0.01;
+// squared pixels, keypoints closer than this are a collapsed detection
+const MIN_VARIANCE: f32 = 1e-4;
+const ARC_FACE_DST: KeyPoints = KeyPoints([
+    [38.2946, 51.6963],
+    [73.5318, 51.5014],
+    [56.0252, 71.7366],
+    [41.5493, 92.3655],
+    [70.7299, 92.2041],
+]);
+
+#[derive(Debug, Clone)]
+pub struct KeyPoints(pub [[f32; 2]; KEY_POINTS_LEN]);
+
+impl KeyPoints {
+    /// Rough keypoints for detectors without landmarks, ArcFace template stretched over `bbox`
+    pub fn from_bbox(bbox: &super::BBox) -> Self {
+        let (w, h) = (bbox.2 - bbox.0, bbox.3 - bbox.1);
+        Self(
+            ARC_FACE_DST
+                .0
+                .map(|[x, y]| [bbox.0 + x / 112. * w, bbox.1 + y / 112. * h]),
+        )
+    }
+
+    fn mean(&self) -> [f32; 2] {
+        Math::mean(self.0)
+    }
+
+    fn scale(&self, ratio: f32) -> Self {
+        Self(self.0.map(|r| [r[0] * ratio, r[1] * ratio]))
+    }
+
+    /// Least squares similarity transform mapping `self` onto `dst`
+    pub fn umeyama(&self, dst: &Self) -> crate::Result<Matrix3<f32>> {
+        self.weighted_umeyama(dst, &[1.; KEY_POINTS_LEN])
+    }
+
+    /// Least median start over leave one out fits, refined by Tukey weighted Umeyama (IRLS).
+    /// A single off keypoint gets rejected instead of skewing the alignment
+    pub fn robust_umeyama(&self, dst: &Self) -> crate::Result<Matrix3<f32>> {
+        let median = |m: &Matrix3<f32>| {
+            let mut residuals = self.residuals(m, dst);
+            residuals.sort_by(f32::total_cmp);
+            residuals[KEY_POINTS_LEN / 2]
+        };
+
+        let full = self.umeyama(dst)?;
+        let mut matrix = (0..KEY_POINTS_LEN)
+            .filter_map(|skip| {
+                let mut weights = [1.; KEY_POINTS_LEN];
+                weights[skip] = 0.;
+                self.weighted_umeyama(dst, &weights).ok()
+            })
+            .fold(
+                full,
+                |best, m| {
+                    if median(&m) < median(&best) {
+                        m
+                    } else {
+                        best
+                    }
+                },
+            );
+        // residual floor, keeps an exact fit from rejecting everything
+        let min_scale = dst.spread() * MIN_RESIDUAL_RATIO;
+
+        for _ in 0..IRLS_ITERATIONS {
+            // median absolute deviation to std
+            let scale = (median(&matrix) * 1.4826).max(min_scale);
+            let weights = self.residuals(&matrix, dst).map(|r| {
+                let u = r / (TUKEY_C * scale);
+                if u < 1. {
+                    (1. - u * u).powi(2)
+                } else {
+                    0.
+                }
+            });
+            if weights.iter().filter(|w| **w > 0.).count() < MIN_INLIERS {
+                break;
+            }
+            matrix = self.weighted_umeyama(dst, &weights)?;
+        }
+        Ok(matrix)
+    }
+
+    pub fn umeyama_to_arc(&self, max_dim: usize) -> crate::Result<Matrix3<f32>> {
+        let ratio = max_dim as f32 / 112.;
+        self.robust_umeyama(&ARC_FACE_DST.scale(ratio))
+    }
+
+    // https://web.stanford.edu/class/cs273/refs/umeyama.pdf
+    fn weighted_umeyama(
+        &self,
+        dst: &Self,
+        weights: &[f32; KEY_POINTS_LEN],
+    ) -> crate::Result<Matrix3<f32>> {
+        use nalgebra::{Matrix2, Vector2};
+
+        if self
+            .iter()
+            .chain(dst.iter())
+            .flatten()
+            .any(|v| !v.is_finite())
+        {
+            return Err(crate::Error::TransformError(format!(
+                "Non finite keypoints: {:?}",
+                self.0
+            )));
+        }
+        let weight_sum = weights.iter().sum::<f32>();
+        if !weight_sum.is_normal() || weights.iter().any(|w| *w < 0.) {
+            return Err(crate::Error::TransformError(format!(
+                "Invalid keypoint weights: {:?}",
+                weights
+            )));
+        }
+
+        let points = |kps: &Self| kps.0.map(|[x, y]| Vector2::new(x, y));
+        let (src, dst) = (points(self), points(dst));
+        let weighted_mean = |pts: &[Vector2<f32>; KEY_POINTS_LEN]| {
+            pts.iter()
+                .zip(weights)
+                .fold(Vector2::zeros(), |accu, (p, w)| accu + p * *w)
+                / weight_sum
+        };
+        let (src_mean, dst_mean) = (weighted_mean(&src), weighted_mean(&dst));
+
+        let (covariance, src_var) = src.iter().zip(dst.iter()).zip(weights).fold(
+            (Matrix2::<f32>::zeros(), 0.),
+            |(cov, var), ((s, d), w)| {
+                let (s, d) = (s - src_mean, d - dst_mean);
+                (cov + (d * s.transpose()) * *w, var + s.norm_squared() * w)
+            },
+        );
+        let (covariance, src_var) = (covariance / weight_sum, src_var / weight_sum);
+        if src_var < MIN_VARIANCE {
+            return Err(crate::Error::TransformError(format!(
+                "Collapsed keypoints: {:?}",
+                self.0
+            )));
+        }
+
+        let svd = covariance.svd(true, true);
+        let (Some(u), Some(v_t)) = (svd.u, svd.v_t) else {
+            return Err(crate::Error::TransformError(
+                "Failed to decompose keypoint covariance".into(),
+            ));
+        };
+        // reflection correction
+        let d = if u.determinant() * v_t.determinant() < 0. {
+            -1.
+        } else {
+            1.
+        };
+        let rotation = u * Matrix2::new(1., 0., 0., d) * v_t;
+        let scale = (svd.singular_values[0] + d * svd.singular_values[1]) / src_var;
+        if !scale.is_normal() || scale < 0. {
+            return Err(crate::Error::TransformError(format!(
+                "Degenerate keypoint scale: {}",
+                scale
+            )));
+        }
+
+        let linear = rotation * scale;
+        let translation = dst_mean - linear * src_mean;
+        Ok(Matrix3::new(
+            linear.m11,
+            linear.m12,
+            translation.x,
+            linear.m21,
+            linear.m22,
+            translation.y,
+            0.,
+            0.,
+            1.,
+        ))
+    }
+
+    /// Distance of each transformed keypoint to its `dst` counterpart
+    fn residuals(&self, matrix: &Matrix3<f32>, dst: &Self) -> [f32; KEY_POINTS_LEN] {
+        std::array::from_fn(|idx| {
+            let [x, y] = self.0[idx];
+            let p = matrix * nalgebra::Vector3::new(x, y, 1.);
+            ((p.x - dst.0[idx][0]).powi(2) + (p.y - dst.0[idx][1]).powi(2)).sqrt()
+        })
+    }
+
+    /// Root mean squared distance to the mean
+    fn spread(&self) -> f32 {
+        let [mx, my] = self.mean();
+        (self
+            .iter()
+            .map(|[x, y]| (x - mx).powi(2) + (y - my).powi(2))
+            .sum::<f32>()
+            / KEY_POINTS_LEN as f32)
+            .sqrt()
+    }
+}
+
+impl std::ops::Deref for KeyPoints {
+    type Target = [[f32; 2]; KEY_POINTS_LEN];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl std::ops::DerefMut for KeyPoints {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Matrix3;
+
+    use super::{KeyPoints, ARC_FACE_DST};
+
+    // rotate by 0.3 rad, scale by 1.7, translate by (40, -12)
+    fn transform(kps: &KeyPoints) -> (KeyPoints, Matrix3<f32>) {
+        let (sin, cos) = 0.3f32.sin_cos();
+        let matrix = Matrix3::new(
+            1.7 * cos,
+            -1.7 * sin,
+            40.,
+            1.7 * sin,
+            1.7 * cos,
+            -12.,
+            0.,
+            0.,
+            1.,
+        );
+        let moved = KeyPoints(kps.map(|[x, y]| {
+            let p = matrix * nalgebra::Vector3::new(x, y, 1.);
+            [p.x, p.y]
+        }));
+        (moved, matrix)
+    }
+
+    #[test]
+    fn umeyama_recovers_similarity_transform() {
+        let (dst, expected) = transform(&ARC_FACE_DST);
+        for matrix in [
+            ARC_FACE_DST.umeyama(&dst).expect("Failed to fit"),
+            ARC_FACE_DST.robust_umeyama(&dst).expect("Failed to fit"),
+        ] {
+            assert!(
+                (matrix - expected).abs().max() < 1e-3,
+                "Expected {:?}, got {:?}",
+                expected,
+                matrix
+            );
+        }
+    }
+
+    #[test]
+    fn robust_umeyama_rejects_outlier_keypoint() {
+        let (mut dst, expected) = transform(&ARC_FACE_DST);
+        // mouth corner off by 40px
+        dst[4][0] += 40.;
+
+        let plain = ARC_FACE_DST.umeyama(&dst).expect("Failed to fit");
+        let robust = ARC_FACE_DST.robust_umeyama(&dst).expect("Failed to fit");
+        let inlier_error = |m: &Matrix3<f32>| {
+            ARC_FACE_DST.residuals(m, &transform(&ARC_FACE_DST).0)[..4]
+                .iter()
+                .fold(0f32, |accu, r| accu.max(*r))
+        };
+
+        assert!(inlier_error(&robust) < 0.1, "Robust fit off inliers");
+        assert!(inlier_error(&plain) > inlier_error(&robust));
+        assert!((robust - expected).abs().max() < 1e-2);
+    }
+
+    #[test]
+    fn fails_on_degenerate_keypoints() {
+        let collapsed = KeyPoints([[20., 20.]; 5]);
+        assert!(collapsed.umeyama(&ARC_FACE_DST).is_err());
+        assert!(collapsed.umeyama_to_arc(112).is_err());
+
+        let mut non_finite = ARC_FACE_DST.clone();
+        non_finite[2][1] = f32::NAN;
+        assert!(non_finite.robust_umeyama(&ARC_FACE_DST).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+// Head pose from the 5 detection keypoints, PnP against a generic face
+// https://docs.opencv.org/4.x/d5/d1f/calib3d_solvePnP.html
+
+use opencv::{core, prelude::*};
+
+use crate::{Error, Result};
+
+use super::KeyPoints;
+
+// mm, nose tip at origin, x right | y down | z away from the camera (cv camera axes)
+// same order as the detected keypoints, eyes | nose | mouth corners
+const CANONICAL_FACE: [[f64; 3]; 5] = [
+    [-31., -35., 30.],
+    [31., -35., 30.],
+    [0., 0., 0.],
+    [-24., 30., 22.],
+    [24., 30., 22.],
+];
+
+/// Degrees, 0 when facing the camera
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct HeadPose {
+    /// Turning left / right
+    pub yaw: f32,
+    /// Nodding up / down
+    pub pitch: f32,
+    /// Tilting towards a shoulder
+    pub roll: f32,
+}
+
+impl HeadPose {
+    /// Solves the pose of `keypoints` in a (w, h) frame, focal length approximated by frame width
+    pub fn estimate(keypoints: &KeyPoints, frame_size: (usize, usize)) -> Result<Self> {
+        if keypoints.iter().flatten().any(|v| !v.is_finite()) {
+            return Err(Error::TransformError(format!(
+                "Non finite keypoints: {:?}",
+                keypoints.0
+            )));
+        }
+
+        let object_points = CANONICAL_FACE
+            .iter()
+            .map(|[x, y, z]| core::Point3d::new(*x, *y, *z))
+            .collect::<core::Vector<core::Point3d>>();
+        let image_points = keypoints
+            .iter()
+            .map(|[x, y]| core::Point2d::new(*x as f64, *y as f64))
+            .collect::<core::Vector<core::Point2d>>();
+
+        let (w, h) = (frame_size.0 as f64, frame_size.1 as f64);
+        let camera =
+            core::Mat::from_slice_2d::<f64>(&[[w, 0., w / 2.], [0., w, h / 2.], [0., 0., 1.]])
+                .map_err(Error::CVError)?;
+
+        let (mut rvec, mut tvec) = (core::Mat::default(), core::Mat::default());
+        let solved = opencv::calib3d::solve_pnp(
+            &object_points,
+            &image_points,
+            &camera,
+            // no distortion
+            &core::Mat::default(),
+            &mut rvec,
+            &mut tvec,
+            false,
+            opencv::calib3d::SOLVEPNP_SQPNP,
+        )
+        .map_err(Error::CVError)?;
+        if !solved {
+            return Err(Error::TransformError("Failed to solve head pose".into()));
+        }
+
+        let rvec = rvec.data_typed::<f64>().map_err(Error::CVError)?;
+        let [x, y, z] = [0, 1, 2].map(|idx| rvec.get(idx).copied().unwrap_or_default() as f32);
+        Ok(Self::from_rotation(
+            nalgebra::Rotation3::new(nalgebra::Vector3::new(x, y, z)).matrix(),
+        ))
+    }
+
+    /// Euler angles of a model to camera rotation, R = Rz(roll) * Ry(yaw) * Rx(pitch)
+    pub fn from_rotation(r: &nalgebra::Matrix3<f32>) -> Self {
+        let sy = (r.m11 * r.m11 + r.m21 * r.m21).sqrt();
+        let (yaw, pitch, roll) = if sy > 1e-6 {
+            ((-r.m31).atan2(sy), r.m32.atan2(r.m33), r.m21.atan2(r.m11))
+        } else {
+            // gimbal lock, roll folded into pitch
+            ((-r.m31).atan2(sy), (-r.m23).atan2(r.m22), 0.)
+        };
+        Self {
+            yaw: yaw.to_degrees(),
+            pitch: pitch.to_degrees(),
+            roll: roll.to_degrees(),
+        }
+    }
+}
+
+/// Yaw & pitch past which the swap fades out and gets skipped
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct PoseLimits {
+    /// Degrees
+    pub max_yaw: f32,
+    /// Degrees
+    pub max_pitch: f32,
+    /// Degrees before a limit the swap starts fading out, 0 cuts hard
+    pub fade: f32,
+}
+
+impl Default for PoseLimits {
+    fn default() -> Self {
+        Self {
+            max_yaw: 50.,
+            max_pitch: 35.,
+            fade: 10.,
+        }
+    }
+}
+
+impl PoseLimits {
+    /// 0 ~ 1 opacity of the swapped face
+    pub fn weight(&self, pose: &HeadPose) -> f32 {
+        let axis = |angle: f32, limit: f32| {
+            if self.fade <= 0. {
+                return if angle.abs() <= limit { 1. } else { 0. };
+            }
+            ((limit - angle.abs()) / self.fade).clamp(0., 1.)
+        };
+        axis(pose.yaw, self.max_yaw).min(axis(pose.pitch, self.max_pitch))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Rotation3, Vector3};
+
+    use super::{HeadPose, PoseLimits};
+
+    #[test]
+    fn recovers_euler_angles_from_rotation() {
+        let (yaw, pitch, roll) = (30f32, -15f32, 10f32);
+        let r = Rotation3::from_axis_angle(&Vector3::z_axis(), roll.to_radians())
+            * Rotation3::from_axis_angle(&Vector3::y_axis(), yaw.to_radians())
+            * Rotation3::from_axis_angle(&Vector3::x_axis(), pitch.to_radians());
+
+        let pose = HeadPose::from_rotation(r.matrix());
+        for (got, expected) in [(pose.yaw, yaw), (pose.pitch, pitch), (pose.roll, roll)] {
+            assert!(
+                (got - expected).abs() < 1e-3,
+                "Expected {}, got {}",
+                expected,
+                got
+            );
+        }
+        assert_eq!(
+            HeadPose::from_rotation(&nalgebra::Matrix3::identity()),
+            HeadPose::default()
+        );
+    }
+
+    #[test]
+    fn fades_swap_towards_limits() {
+        let limits = PoseLimits {
+            max_yaw: 50.,
+            max_pitch: 30.,
+            fade: 10.,
+        };
+        let pose = |yaw, pitch| HeadPose {
+            yaw,
+            pitch,
+            roll: 0.,
+        };
+
+        assert_eq!(limits.weight(&pose(0., 0.)), 1.);
+        assert_eq!(limits.weight(&pose(-45., 0.)), 0.5);
+        assert_eq!(limits.weight(&pose(10., 25.)), 0.5);
+        assert_eq!(limits.weight(&pose(60., 0.)), 0.);
+
+        let hard = PoseLimits { fade: 0., ..limits };
+        assert_eq!(hard.weight(&pose(49., 0.)), 1.);
+        assert_eq!(hard.weight(&pose(0., -31.)), 0.);
+    }
+}
@@ -0,0 +1,448 @@
+// NumPy .npy / .npz format for diffing against python insightface intermediates
+// https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, Write},
+    path::Path,
+};
+
+use crate::{Error, Result};
+
+use super::{ChannelOrder, Normal, RecgnData, Tensor, VectorizedTensor};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+// magic + version + header len
+const PREAMBLE_LEN: usize = 10;
+const DATA_KEY: &str = "data";
+
+pub type NpyArray = ndarray::ArrayD<f32>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NpyValue {
+    Array(NpyArray),
+    Str(String),
+}
+
+/// Types that can be dumped to / loaded from `.npy` (data only) & `.npz` (data + metadata)
+pub trait Npy: Sized {
+    fn to_npy_entries(&self) -> Vec<(&'static str, NpyValue)>;
+
+    fn from_npy_entries(entries: HashMap<String, NpyValue>) -> Result<Self>;
+
+    fn write_npy(&self, path: impl AsRef<Path>) -> Result<()> {
+        let Some((_, NpyValue::Array(data))) = self
+            .to_npy_entries()
+            .into_iter()
+            .find(|(key, _)| *key == DATA_KEY)
+        else {
+            return Err(Error::NpyError("Missing data entry".into()));
+        };
+        write_npy(create_file(path)?, &data)
+    }
+
+    fn read_npy(path: impl AsRef<Path>) -> Result<Self> {
+        let value = read_npy(std::fs::File::open(path).map_err(Error::as_unknown_error)?)?;
+        Self::from_npy_entries(HashMap::from([(DATA_KEY.to_string(), value)]))
+    }
+
+    fn write_npz(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_npz(create_file(path)?, &self.to_npy_entries())
+    }
+
+    fn read_npz(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_npy_entries(read_npz(
+            std::fs::File::open(path).map_err(Error::as_unknown_error)?,
+        )?)
+    }
+}
+
+impl Npy for Tensor {
+    fn to_npy_entries(&self) -> Vec<(&'static str, NpyValue)> {
+        let channels = self.dim().1;
+        let (mean, std): (Vec<f32>, Vec<f32>) =
+            (0..channels).map(|c| self.normal.mean_std(c)).unzip();
+        vec![
+            (DATA_KEY, NpyValue::Array(self.data.clone().into_dyn())),
+            (
+                "mean",
+                NpyValue::Array(ndarray::Array::from(mean).into_dyn()),
+            ),
+            ("std", NpyValue::Array(ndarray::Array::from(std).into_dyn())),
+            (
+                "order",
+                NpyValue::Str(
+                    match self.order {
+                        ChannelOrder::Rgb => "rgb",
+                        ChannelOrder::Bgr => "bgr",
+                    }
+                    .into(),
+                ),
+            ),
+        ]
+    }
+
+    fn from_npy_entries(mut entries: HashMap<String, NpyValue>) -> Result<Self> {
+        let data = take_array(&mut entries, DATA_KEY)?;
+        let data = match data.ndim() {
+            // (c, h, w)
+            3 => data.insert_axis(ndarray::Axis(0)),
+            4 => data,
+            n => {
+                return Err(Error::NpyError(format!(
+                    "Expected (n, c, h, w) tensor, got {} dimensions",
+                    n
+                )))
+            }
+        }
+        .into_dimensionality::<ndarray::Ix4>()
+        .map_err(Error::as_unknown_error)?;
+
+        let mut tensor = Tensor::from(data);
+        if let (Ok(mean), Ok(std)) = (
+            take_array(&mut entries, "mean"),
+            take_array(&mut entries, "std"),
+        ) {
+            tensor.normal =
+                normal_from_mean_std(mean.into_iter().collect(), std.into_iter().collect());
+        }
+        if let Some(NpyValue::Str(order)) = entries.remove("order") {
+            tensor.order = match order.to_lowercase().as_str() {
+                "bgr" => ChannelOrder::Bgr,
+                _ => ChannelOrder::Rgb,
+            };
+        }
+        Ok(tensor)
+    }
+}
+
+impl Npy for VectorizedTensor {
+    fn to_npy_entries(&self) -> Vec<(&'static str, NpyValue)> {
+        vec![(DATA_KEY, NpyValue::Array(self.0.clone().into_dyn()))]
+    }
+
+    fn from_npy_entries(mut entries: HashMap<String, NpyValue>) -> Result<Self> {
+        Ok(Self(into_2d(take_array(&mut entries, DATA_KEY)?)?))
+    }
+}
+
+impl Npy for RecgnData {
+    fn to_npy_entries(&self) -> Vec<(&'static str, NpyValue)> {
+        vec![(DATA_KEY, NpyValue::Array(self.0.clone().into_dyn()))]
+    }
+
+    fn from_npy_entries(mut entries: HashMap<String, NpyValue>) -> Result<Self> {
+        Ok(Self(into_2d(take_array(&mut entries, DATA_KEY)?)?))
+    }
+}
+
+/// Writes `<f4` C ordered array
+pub fn write_npy<W: Write>(mut w: W, array: &NpyArray) -> Result<()> {
+    let shape = match array.shape() {
+        [] => "()".to_string(),
+        [len] => format!("({},)", len),
+        dims => format!(
+            "({})",
+            dims.iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    };
+    write_header(
+        &mut w,
+        &format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}",
+            shape
+        ),
+    )?;
+    // logical iteration is C order regardless of memory layout
+    let bytes = array
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+    w.write_all(&bytes).map_err(Error::as_unknown_error)
+}
+
+/// Writes 0 dimension unicode `<U` array
+pub fn write_str_npy<W: Write>(mut w: W, value: &str) -> Result<()> {
+    let mut bytes = value
+        .chars()
+        .flat_map(|c| (c as u32).to_le_bytes())
+        .collect::<Vec<u8>>();
+    if bytes.is_empty() {
+        bytes = vec![0; 4];
+    }
+    write_header(
+        &mut w,
+        &format!(
+            "{{'descr': '<U{}', 'fortran_order': False, 'shape': (), }}",
+            bytes.len() / 4
+        ),
+    )?;
+    w.write_all(&bytes).map_err(Error::as_unknown_error)
+}
+
+pub fn read_npy<R: Read>(mut r: R) -> Result<NpyValue> {
+    let mut bytes = vec![];
+    r.read_to_end(&mut bytes).map_err(Error::as_unknown_error)?;
+    if bytes.len() < PREAMBLE_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(Error::NpyError("Missing npy magic string".into()));
+    }
+
+    let (header_len, offset) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+            12,
+        ),
+        version => {
+            return Err(Error::NpyError(format!(
+                "Unsupported npy version: {}",
+                version
+            )))
+        }
+    };
+    let header = bytes
+        .get(offset..offset + header_len)
+        .and_then(|h| std::str::from_utf8(h).ok())
+        .ok_or_else(|| Error::NpyError("Invalid npy header".into()))?;
+
+    let descr = header_field(header, "descr")?
+        .trim_start_matches(['\'', '"'])
+        .split(['\'', '"'])
+        .next()
+        .unwrap_or_default();
+    let fortran_order = header_field(header, "fortran_order")?.starts_with("True");
+    let shape = header_field(header, "shape")?
+        .trim_start_matches('(')
+        .split(')')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| d.parse::<usize>().map_err(Error::as_unknown_error))
+        .collect::<Result<Vec<usize>>>()?;
+
+    let body = &bytes[offset + header_len..];
+    if let Some(len) = descr.strip_prefix("<U") {
+        let len = len.parse::<usize>().map_err(Error::as_unknown_error)?;
+        return Ok(NpyValue::Str(
+            body.chunks_exact(4)
+                .take(len)
+                .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+                .take_while(|c| *c != 0)
+                .filter_map(char::from_u32)
+                .collect(),
+        ));
+    }
+
+    let values = match descr {
+        "<f4" => le_values(body, |c: [u8; 4]| f32::from_le_bytes(c)),
+        "<f8" => le_values(body, |c: [u8; 8]| f64::from_le_bytes(c) as f32),
+        "|u1" | "<u1" => le_values(body, |c: [u8; 1]| c[0] as f32),
+        "|i1" | "<i1" => le_values(body, |c: [u8; 1]| c[0] as i8 as f32),
+        "|b1" => le_values(body, |c: [u8; 1]| (c[0] != 0) as u8 as f32),
+        "<i4" => le_values(body, |c: [u8; 4]| i32::from_le_bytes(c) as f32),
+        "<i8" => le_values(body, |c: [u8; 8]| i64::from_le_bytes(c) as f32),
+        dtype => return Err(Error::NpyError(format!("Unsupported npy dtype: {}", dtype))),
+    };
+
+    let len = shape.iter().product::<usize>();
+    if values.len() < len {
+        return Err(Error::NpyError(format!(
+            "Expected {} values for shape {:?}, got {}",
+            len,
+            shape,
+            values.len()
+        )));
+    }
+    let values = values[..len].to_vec();
+
+    let array = if fortran_order {
+        let reversed = shape.iter().rev().copied().collect::<Vec<usize>>();
+        NpyArray::from_shape_vec(ndarray::IxDyn(&reversed), values)
+            .map_err(Error::as_unknown_error)?
+            .reversed_axes()
+            .as_standard_layout()
+            .into_owned()
+    } else {
+        NpyArray::from_shape_vec(ndarray::IxDyn(&shape), values).map_err(Error::as_unknown_error)?
+    };
+    Ok(NpyValue::Array(array))
+}
+
+/// np.savez layout, uncompressed `<key>.npy` entries
+pub fn write_npz<W: Write + Seek>(w: W, entries: &[(&str, NpyValue)]) -> Result<()> {
+    let mut zip = zip::ZipWriter::new(w);
+    for (key, value) in entries {
+        zip.start_file(
+            format!("{}.npy", key),
+            zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored),
+        )
+        .map_err(Error::as_unknown_error)?;
+        match value {
+            NpyValue::Array(array) => write_npy(&mut zip, array)?,
+            NpyValue::Str(value) => write_str_npy(&mut zip, value)?,
+        }
+    }
+    zip.finish().map_err(Error::as_unknown_error)?;
+    Ok(())
+}
+
+/// Reads both np.savez & np.savez_compressed archives
+pub fn read_npz<R: Read + Seek>(r: R) -> Result<HashMap<String, NpyValue>> {
+    let mut zip = zip::ZipArchive::new(r).map_err(Error::as_unknown_error)?;
+    (0..zip.len())
+        .map(|idx| {
+            let file = zip.by_index(idx).map_err(Error::as_unknown_error)?;
+            let key = file.name().trim_end_matches(".npy").to_string();
+            Ok((key, read_npy(file)?))
+        })
+        .collect()
+}
+
+fn write_header<W: Write>(w: &mut W, header: &str) -> Result<()> {
+    // header ends with '\n' and data starts 64 byte aligned
+    let unpadded = PREAMBLE_LEN + header.len() + 1;
+    let header = format!("{}{}\n", header, " ".repeat((64 - unpadded % 64) % 64));
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend([1, 0]);
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    w.write_all(&bytes).map_err(Error::as_unknown_error)
+}
+
+fn header_field<'a>(header: &'a str, key: &str) -> Result<&'a str> {
+    let key = format!("'{}':", key);
+    let start = header
+        .find(&key)
+        .ok_or_else(|| Error::NpyError(format!("Missing {} in npy header", key)))?;
+    Ok(header[start + key.len()..].trim_start())
+}
+
+fn le_values<const N: usize>(body: &[u8], f: impl Fn([u8; N]) -> f32) -> Vec<f32> {
+    body.chunks_exact(N)
+        .map(|c| f(c.try_into().unwrap_or([0; N])))
+        .collect()
+}
+
+fn create_file(path: impl AsRef<Path>) -> Result<std::fs::File> {
+    std::fs::File::create(path).map_err(Error::as_unknown_error)
+}
+
+fn take_array(entries: &mut HashMap<String, NpyValue>, key: &str) -> Result<NpyArray> {
+    match entries.remove(key) {
+        Some(NpyValue::Array(array)) => Ok(array),
+        _ => Err(Error::NpyError(format!("Missing {} array", key))),
+    }
+}
+
+fn into_2d(array: NpyArray) -> Result<ndarray::Array2<f32>> {
+    match array.ndim() {
+        // (512,) embeddings
+        1 => array.insert_axis(ndarray::Axis(0)),
+        _ => array,
+    }
+    .into_dimensionality::<ndarray::Ix2>()
+    .map_err(Error::as_unknown_error)
+}
+
+fn normal_from_mean_std(mean: Vec<f32>, std: Vec<f32>) -> Normal {
+    [Normal::N1ToP1, Normal::ZeroToP1, Normal::U8]
+        .into_iter()
+        .find(|preset| {
+            mean.iter().all(|m| *m == preset.mean_std(0).0)
+                && std.iter().all(|s| *s == preset.mean_std(0).1)
+        })
+        .unwrap_or(Normal::MeanStd { mean, std })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use rand::Rng;
+
+    use super::{read_npy, read_npz, write_npy, Npy, NpyValue};
+    use crate::model::{
+        data::{ChannelOrder, Normal, VectorizedTensor},
+        Tensor, TensorData,
+    };
+
+    #[test]
+    fn can_round_trip_npy_array() {
+        let mut rand = rand::thread_rng();
+        let array = ndarray::ArrayD::from_shape_fn(ndarray::IxDyn(&[2, 3, 5]), |_| rand.gen());
+        let mut bytes = vec![];
+        write_npy(&mut bytes, &array).expect("Failed to write npy");
+
+        // data is 64 byte aligned
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+
+        assert_eq!(
+            read_npy(bytes.as_slice()).expect("Failed to read npy"),
+            NpyValue::Array(array)
+        );
+    }
+
+    #[test]
+    fn can_read_fortran_ordered_f8_npy() {
+        let header = "{'descr': '<f8', 'fortran_order': True, 'shape': (2, 3), }";
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16 + 1).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        bytes.push(b'\n');
+        // column major [[0, 1, 2], [3, 4, 5]]
+        for v in [0f64, 3., 1., 4., 2., 5.] {
+            bytes.extend(v.to_le_bytes());
+        }
+
+        let NpyValue::Array(array) = read_npy(bytes.as_slice()).expect("Failed to read npy") else {
+            panic!("Expected array value");
+        };
+        assert_eq!(array.shape(), &[2, 3]);
+        assert_eq!(
+            array.iter().copied().collect::<Vec<f32>>(),
+            [0., 1., 2., 3., 4., 5.]
+        );
+    }
+
+    #[test]
+    fn can_round_trip_tensor_npz_with_metadata() {
+        let mut rand = rand::thread_rng();
+        let tensor = Tensor::new(
+            Normal::imagenet(),
+            TensorData::from_shape_fn((2, 3, 4, 6), |_| rand.gen()),
+        )
+        .with_order(ChannelOrder::Bgr);
+
+        let mut buf = Cursor::new(vec![]);
+        super::write_npz(&mut buf, &tensor.to_npy_entries()).expect("Failed to write npz");
+        buf.set_position(0);
+        let loaded = Tensor::from_npy_entries(read_npz(buf).expect("Failed to read npz"))
+            .expect("Failed to load tensor");
+
+        assert_eq!(loaded.data, tensor.data);
+        assert_eq!(loaded.normal, tensor.normal);
+        assert_eq!(loaded.order, ChannelOrder::Bgr);
+    }
+
+    #[test]
+    fn can_round_trip_vectorized_tensor_file() {
+        let mut rand = rand::thread_rng();
+        let vec_tensor =
+            VectorizedTensor::new(ndarray::Array2::from_shape_fn((1, 512), |_| rand.gen()));
+        let path = std::env::temp_dir().join(format!("noface_vec_{}.npy", rand.gen::<u32>()));
+
+        vec_tensor.write_npy(&path).expect("Failed to write npy");
+        let loaded = VectorizedTensor::read_npy(&path).expect("Failed to read npy");
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(loaded.0, vec_tensor.0);
+    }
+}
@@ -1,442 +1,889 @@
-use crate::model::InputSizeMatrix;
-
-// (n, c, h, w)
-pub type TensorData = ndarray::Array<f32, ndarray::Dim<[usize; 4]>>;
-
-#[derive(Debug, PartialEq, Clone)]
-pub enum Normal {
-    /// Negative One To Plus One
-    N1ToP1,
-    /// Zero to Plus One
-    ZeroToP1,
-    /// Zero to 255
-    U8,
-}
-
-#[derive(Debug, Clone)]
-pub struct Tensor {
-    pub normal: Normal,
-    pub data: TensorData,
-}
-
-impl Default for Tensor {
-    fn default() -> Self {
-        Self {
-            normal: Normal::N1ToP1,
-            data: ndarray::Array::zeros((1, 3, 128, 128)),
-        }
-    }
-}
-
-impl Tensor {
-    pub fn new(normal: Normal, array: TensorData) -> Self {
-        Self {
-            normal,
-            data: array,
-        }
-    }
-
-    pub fn is_eq_dim(&self, cmp_dim: (usize, usize, usize, usize)) -> bool {
-        let dim = self.dim();
-        dim.0 == cmp_dim.0 && dim.1 == cmp_dim.1 && dim.2 == cmp_dim.2 && dim.3 == cmp_dim.3
-    }
-
-    pub fn to_normalization(&mut self, n: Normal) {
-        let curr_normalization = self.normal.clone();
-        if curr_normalization == n {
-            return;
-        }
-        self.par_mapv_inplace(|v| match curr_normalization {
-            Normal::N1ToP1 => match n {
-                Normal::ZeroToP1 => v / 2. + 0.5,
-                Normal::U8 => v * 127.5 + 127.5,
-                Normal::N1ToP1 => v,
-            },
-            Normal::ZeroToP1 => match n {
-                Normal::N1ToP1 => v * 2. - 1.,
-                Normal::U8 => v * 255.,
-                Normal::ZeroToP1 => v,
-            },
-            Normal::U8 => match n {
-                Normal::N1ToP1 => (v - 127.5) / 127.5,
-                Normal::ZeroToP1 => v / 255.,
-                Normal::U8 => v,
-            },
-        });
-        self.normal = n;
-    }
-
-    pub fn resize(&self, size: (usize, usize)) -> Self {
-        let mut new_mat =
-            ndarray::Array::<(usize, usize, usize, usize), ndarray::Dim<[usize; 4]>>::from_shape_fn(
-                (1, 3, size.1, size.0),
-                |dim| dim,
-            );
-        self.resize_with_matrix(&mut new_mat)
-    }
-
-    pub fn resize_with_matrix(&self, input_mat: &mut InputSizeMatrix) -> Self {
-        let (_, _, cur_y, cur_x) = self.dim();
-        let (_, _, i_y, i_x) = input_mat.dim();
-        if cur_x == 0 || cur_y == 0 {
-            return Self {
-                normal: self.normal.clone(),
-                data: TensorData::zeros((1, 3, i_y, i_x)),
-            };
-        }
-
-        let (x_scale_factor, y_scale_factor) = (
-            if i_x != 0 {
-                cur_x as f32 / i_x as f32
-            } else {
-                0.
-            },
-            if i_y != 0 {
-                cur_y as f32 / i_y as f32
-            } else {
-                0.
-            },
-        );
-
-        let new_tensor = ndarray::Zip::from(input_mat).par_map_collect(|(n, c, y, x)| {
-            // new x & new y
-            let (nx, ny) = ((*x as f32) * x_scale_factor, (*y as f32) * y_scale_factor);
-            let (x_floor, x_ceil) = (
-                nx.floor() as usize,
-                std::cmp::min(nx.ceil() as usize, cur_x - 1),
-            );
-            let (y_floor, y_ceil) = (
-                ny.floor() as usize,
-                std::cmp::min(ny.ceil() as usize, cur_y - 1),
-            );
-
-            if x_ceil == x_floor && y_ceil == y_floor {
-                return self[(*n, *c, ny as usize, nx as usize)];
-            }
-
-            if x_ceil == x_floor {
-                let (q1, q2) = (
-                    self[(*n, *c, y_floor, nx as usize)],
-                    self[(*n, *c, y_ceil, nx as usize)],
-                );
-                return q1 * (y_ceil as f32 - ny) + q2 * (ny - y_floor as f32);
-            }
-
-            if y_ceil == y_floor {
-                let (q1, q2) = (
-                    self[(*n, *c, ny as usize, x_floor)],
-                    self[(*n, *c, ny as usize, x_ceil)],
-                );
-                return q1 * (x_ceil as f32 - nx) + q2 * (nx - x_floor as f32);
-            }
-
-            // corner values
-            let (v1, v2, v3, v4) = (
-                self[(*n, *c, y_floor, x_floor)],
-                self[(*n, *c, y_floor, x_ceil)],
-                self[(*n, *c, y_ceil, x_floor)],
-                self[(*n, *c, y_ceil, x_ceil)],
-            );
-            let (q1, q2) = (
-                v1 * (x_ceil as f32 - nx) + v2 * (nx - x_floor as f32),
-                v3 * (x_ceil as f32 - nx) + v4 * (nx - x_floor as f32),
-            );
-            q1 * (y_ceil as f32 - ny) + q2 * (ny - y_floor as f32)
-        });
-
-        Self {
-            normal: self.normal.clone(),
-            data: new_tensor,
-        }
-    }
-    pub fn to_cuda_slice(
-        self,
-        cuda: &std::sync::Arc<cudarc::driver::CudaDevice>,
-    ) -> crate::Result<cudarc::driver::CudaSlice<f32>> {
-        cuda.htod_sync_copy(&self.data.into_raw_vec_and_offset().0)
-            .map_err(crate::Error::CudaError)
-    }
-
-    pub fn mean(&self) -> f32 {
-        let (_, c, y, x) = self.dim();
-        self.data.flatten().sum() / (c * y * x) as f32
-    }
-
-    pub fn norm(&self) -> f32 {
-        self.flatten().map(|v| v * v).sum().sqrt()
-    }
-
-    pub fn transpose(
-        &mut self,
-        mut src: Tensor,
-        bbox: (usize, usize, usize, usize),
-    ) -> crate::Result<()> {
-        let (_, _, tar_y, tar_x) = self.dim();
-        let (_, _, src_y, src_x) = src.dim();
-
-        if self.normal != src.normal {
-            src.to_normalization(self.normal.clone());
-        }
-
-        let (crop_x, crop_y) = (bbox.2 - bbox.0, bbox.3 - bbox.1);
-        if src_x != crop_x || src_y != crop_y {
-            src = src.resize((crop_x, crop_y));
-        }
-
-        for ((n, c, y, x), v) in src.indexed_iter() {
-            if (bbox.0 + x) > (tar_x - 1) || (bbox.1 + y) > (tar_y - 1) {
-                continue;
-            }
-            self[(n, c, bbox.1 + y, bbox.0 + x)] = *v;
-        }
-
-        Ok(())
-    }
-
-    pub fn border(&mut self, bbox: (usize, usize, usize, usize)) -> crate::Result<()> {
-        let (_, _, tar_y, tar_x) = self.dim();
-
-        let border_color = match self.normal {
-            super::Normal::N1ToP1 => [0., 1., -1.],
-            super::Normal::ZeroToP1 => [0.5, 1., 0.],
-            super::Normal::U8 => [127., 255., 0.],
-        };
-
-        // Draw top and bottom line
-        for x in 0..(bbox.2 - bbox.0) {
-            for c in 0..3 {
-                let row_x = (bbox.0 + x).min(tar_x - 1);
-                self[(0, c, bbox.1.min(tar_y - 1), row_x)] = border_color[c];
-                self[(0, c, bbox.3.min(tar_y - 1), row_x)] = border_color[c];
-            }
-        }
-        // Draw side lines
-        for y in 0..(bbox.3 - bbox.1) {
-            for c in 0..3 {
-                let col_y = (bbox.1 + y).min(tar_y - 1);
-                self[(0, c, col_y, bbox.0.min(tar_x - 1))] = border_color[c];
-                self[(0, c, col_y, bbox.2.min(tar_x - 1))] = border_color[c];
-            }
-        }
-        Ok(())
-    }
-}
-
-impl From<TensorData> for Tensor {
-    fn from(value: TensorData) -> Self {
-        Self {
-            normal: Normal::ZeroToP1,
-            data: value,
-        }
-    }
-}
-
-impl From<Tensor> for TensorData {
-    fn from(value: Tensor) -> Self {
-        value.data
-    }
-}
-
-impl From<Tensor> for eframe::egui::ImageData {
-    fn from(value: Tensor) -> Self {
-        use eframe::egui::{Color32, ColorImage, ImageData};
-        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-
-        let (multiplier, norm_add) = (
-            match value.normal {
-                Normal::N1ToP1 => 127.5,
-                Normal::ZeroToP1 => 255.,
-                Normal::U8 => 1.,
-            },
-            if value.normal == Normal::N1ToP1 {
-                127.5
-            } else {
-                0.
-            },
-        );
-
-        let (_, _, height, width) = value.dim();
-        ImageData::Color(std::sync::Arc::new(ColorImage {
-            size: [width, height],
-            pixels: (0..width * height)
-                .collect::<Vec<usize>>()
-                .par_iter()
-                .map(|i| {
-                    let (x, y) = (i % width, i / width);
-                    Color32::from_rgba_premultiplied(
-                        (value[[0, 0, y, x]] * multiplier + norm_add) as u8,
-                        (value[[0, 1, y, x]] * multiplier + norm_add) as u8,
-                        (value[[0, 2, y, x]] * multiplier + norm_add) as u8,
-                        255,
-                    )
-                })
-                .collect(),
-        }))
-    }
-}
-
-impl From<Tensor> for crate::image::Image {
-    fn from(mut value: Tensor) -> Self {
-        value.to_normalization(Normal::U8);
-        let (_, _, height, width) = value.dim();
-
-        let (multiplier, norm_add) = (
-            match value.normal {
-                Normal::N1ToP1 => 127.5,
-                Normal::ZeroToP1 => 255.,
-                Normal::U8 => 1.,
-            },
-            if value.normal == Normal::N1ToP1 {
-                127.5
-            } else {
-                0.
-            },
-        );
-        crate::image::Image::from(image::RgbImage::from_par_fn(
-            width as u32,
-            height as u32,
-            |x, y| {
-                image::Rgb([
-                    (value[[0, 0, y as usize, x as usize]] * multiplier + norm_add) as u8,
-                    (value[[0, 1, y as usize, x as usize]] * multiplier + norm_add) as u8,
-                    (value[[0, 2, y as usize, x as usize]] * multiplier + norm_add) as u8,
-                ])
-            },
-        ))
-    }
-}
-
-impl std::ops::Deref for Tensor {
-    type Target = TensorData;
-
-    fn deref(&self) -> &Self::Target {
-        &self.data
-    }
-}
-
-impl std::ops::DerefMut for Tensor {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.data
-    }
-}
-
-#[cfg(test)]
-mod test {
-    use crate::model::TensorData;
-
-    use super::{Normal, Tensor};
-    use rand::Rng;
-
-    #[test]
-    fn can_convert_tensor_data_to_image() {
-        let mut rand = rand::thread_rng();
-        let (w, h) = (128, 128);
-        let tensor = Tensor::from(TensorData::from_shape_fn((1, 3, w, h), |_| rand.gen()));
-        let tensor_img = crate::image::Image::from(tensor.clone());
-        let (rand_x, rand_y, rand_c) = (
-            rand.gen_range(0..w),
-            rand.gen_range(0..h),
-            rand.gen_range(0..3),
-        );
-
-        assert_eq!(
-            (tensor[(0, rand_c, rand_y, rand_x)] * 255.) as u8,
-            tensor_img[(rand_x as u32, rand_y as u32)][rand_c],
-        );
-    }
-
-    #[test]
-    fn can_resize_tensor_data() {
-        let mut rand = rand::thread_rng();
-        let test_data = Tensor::default();
-        let new_size = (
-            rand.gen_range(0..1000) as usize,
-            rand.gen_range(0..1000) as usize,
-        );
-
-        let resized_data = test_data.resize(new_size);
-        let (_, _, new_y, new_x) = resized_data.dim();
-
-        assert_eq!(new_x, new_size.0, "resized width doesn't match");
-        assert_eq!(new_y, new_size.1, "resized height doesn't match");
-
-        assert_eq!(
-            new_size.0 * new_size.1 * 3,
-            resized_data.flatten().len(),
-            "resized tensor byte length doesn't match"
-        );
-    }
-
-    #[test]
-    fn can_convert_tensor_normalization() {
-        let mut rand = rand::thread_rng();
-        let (w, h) = (100, 100);
-        let (rand_x, rand_y, rand_c) = (
-            rand.gen_range(0..w),
-            rand.gen_range(0..h),
-            rand.gen_range(0..3),
-        );
-
-        for mut t in [
-            Tensor::new(
-                Normal::ZeroToP1,
-                TensorData::from_shape_fn((1, 3, h, w), |_| rand.gen()),
-            ),
-            Tensor::new(
-                Normal::N1ToP1,
-                TensorData::from_shape_fn((1, 3, h, w), |_| rand.gen::<f32>() * 2. - 1.),
-            ),
-            Tensor::new(
-                Normal::U8,
-                TensorData::from_shape_fn((1, 3, h, w), |_| rand.gen_range(0..=255) as f32),
-            ),
-        ] {
-            let normalization = t.normal.clone();
-            let t_val = t.data[(0, rand_c, rand_y, rand_x)];
-            if normalization == Normal::ZeroToP1 {
-                t.to_normalization(Normal::N1ToP1);
-                assert_eq!(
-                    t_val * 2. - 1.,
-                    t.data[(0, rand_c, rand_y, rand_x)],
-                    "ZeroToP1 to N1ToP1"
-                );
-                t.to_normalization(normalization.clone());
-                t.to_normalization(Normal::U8);
-                assert_eq!(
-                    t_val * 255.,
-                    t.data[(0, rand_c, rand_y, rand_x)],
-                    "ZeroToP1 to U8"
-                );
-            } else if normalization == Normal::N1ToP1 {
-                t.to_normalization(Normal::ZeroToP1);
-                assert_eq!(
-                    t_val / 2. + 0.5,
-                    t.data[(0, rand_c, rand_y, rand_x)],
-                    "N1ToP1 to ZeroToP1"
-                );
-                t.to_normalization(normalization.clone());
-                t.to_normalization(Normal::U8);
-                assert_eq!(
-                    t_val * 127.5 + 127.5,
-                    t.data[(0, rand_c, rand_y, rand_x)],
-                    "N1ToP1 to U8"
-                );
-            } else {
-                // u8
-                t.to_normalization(Normal::ZeroToP1);
-                assert_eq!(
-                    t_val / 255.,
-                    t.data[(0, rand_c, rand_y, rand_x)],
-                    "U8 to ZeroToP1"
-                );
-                t.to_normalization(normalization.clone());
-                t.to_normalization(Normal::N1ToP1);
-                assert_eq!(
-                    (t_val - 127.5) / 127.5,
-                    t.data[(0, rand_c, rand_y, rand_x)],
-                    "U8 to N1ToP1"
-                );
-            }
-        }
-    }
-}
+pub use border_mode::BorderMode;
+pub use interpolation::Interpolation;
+
+use crate::model::InputSizeMatrix;
+
+pub mod border_mode;
+pub mod interpolation;
+
+// (n, c, h, w)
+pub type TensorData = ndarray::Array<f32, ndarray::Dim<[usize; 4]>>;
+
+#[derive(Debug, PartialEq, Clone, serde::Deserialize, serde::Serialize)]
+pub enum Normal {
+    /// Negative One To Plus One
+    N1ToP1,
+    /// Zero to Plus One
+    ZeroToP1,
+    /// Zero to 255
+    U8,
+    /// (pixel - mean) / std per channel, pixel being 0 ~ 255 & indexed in tensor channel order
+    MeanStd { mean: Vec<f32>, std: Vec<f32> },
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum ChannelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+}
+
+/// Normalization & channel order of a model input
+#[derive(Debug, PartialEq, Clone, serde::Deserialize, serde::Serialize)]
+pub struct InputFormat {
+    pub normal: Normal,
+    pub order: ChannelOrder,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tensor {
+    pub normal: Normal,
+    pub order: ChannelOrder,
+    pub data: TensorData,
+}
+
+impl Normal {
+    /// ImageNet mean & std in RGB order
+    pub fn imagenet() -> Self {
+        Normal::MeanStd {
+            mean: vec![123.675, 116.28, 103.53],
+            std: vec![58.395, 57.12, 57.375],
+        }
+    }
+
+    /// (mean, std) of channel `c` in 0 ~ 255 pixel space
+    pub fn mean_std(&self, c: usize) -> (f32, f32) {
+        match self {
+            Normal::N1ToP1 => (127.5, 127.5),
+            Normal::ZeroToP1 => (0., 255.),
+            Normal::U8 => (0., 1.),
+            Normal::MeanStd { mean, std } => (
+                mean.get(c).or(mean.last()).copied().unwrap_or(0.),
+                std.get(c).or(std.last()).copied().unwrap_or(1.),
+            ),
+        }
+    }
+
+    /// Normalized value of 0 ~ 255 `pixel` in channel `c`
+    pub fn from_pixel(&self, c: usize, pixel: f32) -> f32 {
+        let (mean, std) = self.mean_std(c);
+        (pixel - mean) / std
+    }
+
+    /// 0 ~ 255 pixel of normalized `v` in channel `c`
+    pub fn to_pixel(&self, c: usize, v: f32) -> f32 {
+        let (mean, std) = self.mean_std(c);
+        v * std + mean
+    }
+
+    /// Value of a black pixel in channel `c`
+    pub fn zero_value(&self, c: usize) -> f32 {
+        self.from_pixel(c, 0.)
+    }
+
+    fn is_preset(&self) -> bool {
+        !matches!(self, Normal::MeanStd { .. })
+    }
+
+    fn swap_channels(&mut self) {
+        if let Normal::MeanStd { mean, std } = self {
+            if mean.len() >= 3 {
+                mean.swap(0, 2);
+            }
+            if std.len() >= 3 {
+                std.swap(0, 2);
+            }
+        }
+    }
+}
+
+impl Default for InputFormat {
+    fn default() -> Self {
+        Self {
+            normal: Normal::N1ToP1,
+            order: ChannelOrder::Rgb,
+        }
+    }
+}
+
+impl Default for Tensor {
+    fn default() -> Self {
+        Self {
+            normal: Normal::N1ToP1,
+            order: ChannelOrder::Rgb,
+            data: ndarray::Array::zeros((1, 3, 128, 128)),
+        }
+    }
+}
+
+impl Tensor {
+    pub fn new(normal: Normal, array: TensorData) -> Self {
+        Self {
+            normal,
+            order: ChannelOrder::Rgb,
+            data: array,
+        }
+    }
+
+    pub fn with_order(mut self, order: ChannelOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn is_eq_dim(&self, cmp_dim: (usize, usize, usize, usize)) -> bool {
+        let dim = self.dim();
+        dim.0 == cmp_dim.0 && dim.1 == cmp_dim.1 && dim.2 == cmp_dim.2 && dim.3 == cmp_dim.3
+    }
+
+    pub fn to_normalization(&mut self, n: Normal) {
+        let curr_normalization = self.normal.clone();
+        if curr_normalization == n {
+            return;
+        }
+        if !curr_normalization.is_preset() || !n.is_preset() {
+            for (c, mut channel) in self.data.axis_iter_mut(ndarray::Axis(1)).enumerate() {
+                let ((cur_mean, cur_std), (mean, std)) =
+                    (curr_normalization.mean_std(c), n.mean_std(c));
+                channel.par_mapv_inplace(|v| (v * cur_std + cur_mean - mean) / std);
+            }
+            self.normal = n;
+            return;
+        }
+        self.par_mapv_inplace(|v| match curr_normalization {
+            Normal::N1ToP1 => match n {
+                Normal::ZeroToP1 => v / 2. + 0.5,
+                Normal::U8 => v * 127.5 + 127.5,
+                _ => v,
+            },
+            Normal::ZeroToP1 => match n {
+                Normal::N1ToP1 => v * 2. - 1.,
+                Normal::U8 => v * 255.,
+                _ => v,
+            },
+            Normal::U8 => match n {
+                Normal::N1ToP1 => (v - 127.5) / 127.5,
+                Normal::ZeroToP1 => v / 255.,
+                _ => v,
+            },
+            Normal::MeanStd { .. } => v,
+        });
+        self.normal = n;
+    }
+
+    /// Swaps R & B channels when order differs, no-op below 3 channels
+    pub fn to_channel_order(&mut self, order: ChannelOrder) {
+        if self.order == order {
+            return;
+        }
+        if self.dim().1 >= 3 {
+            let (mut first, mut third) = self
+                .data
+                .multi_slice_mut((ndarray::s![.., 0, .., ..], ndarray::s![.., 2, .., ..]));
+            ndarray::Zip::from(&mut first)
+                .and(&mut third)
+                .par_for_each(std::mem::swap);
+            self.normal.swap_channels();
+        }
+        self.order = order;
+    }
+
+    pub fn to_format(&mut self, format: &InputFormat) {
+        self.to_channel_order(format.order);
+        self.to_normalization(format.normal.clone());
+    }
+
+    pub fn resize(&self, size: (usize, usize), interpolation: Interpolation) -> Self {
+        let (n, c, _, _) = self.dim();
+        let mut new_mat = InputSizeMatrix::from_shape_fn((n, c, size.1, size.0), |dim| dim);
+        self.resize_with_matrix(&mut new_mat, interpolation)
+    }
+
+    pub fn resize_with_matrix(
+        &self,
+        input_mat: &mut InputSizeMatrix,
+        interpolation: Interpolation,
+    ) -> Self {
+        let (cur_n, cur_c, cur_y, cur_x) = self.dim();
+        let (i_n, i_c, i_y, i_x) = input_mat.dim();
+        // cached input matrix built for another batch / channel size
+        if i_n != cur_n || i_c != cur_c {
+            *input_mat = InputSizeMatrix::from_shape_fn((cur_n, cur_c, i_y, i_x), |dim| dim);
+        }
+        if cur_x == 0 || cur_y == 0 {
+            return Self {
+                normal: self.normal.clone(),
+                order: self.order,
+                data: TensorData::zeros((cur_n, cur_c, i_y, i_x)),
+            };
+        }
+
+        // separable kernel, taps are shared by every row / column
+        let (x_taps, y_taps) = (
+            interpolation.taps(cur_x, i_x),
+            interpolation.taps(cur_y, i_y),
+        );
+
+        let new_tensor = ndarray::Zip::from(input_mat).par_map_collect(|(n, c, y, x)| {
+            y_taps[*y].iter().fold(0., |y_accu, (src_y, y_weight)| {
+                y_accu
+                    + y_weight
+                        * x_taps[*x].iter().fold(0., |x_accu, (src_x, x_weight)| {
+                            x_accu + x_weight * self[(*n, *c, *src_y, *src_x)]
+                        })
+            })
+        });
+
+        Self {
+            normal: self.normal.clone(),
+            order: self.order,
+            data: new_tensor,
+        }
+    }
+
+    /// Black border on the right & bottom up to (w, h), the content keeps its coordinates
+    pub fn pad(&self, size: (usize, usize)) -> Self {
+        let (n, c, h, w) = self.dim();
+        Self {
+            normal: self.normal.clone(),
+            order: self.order,
+            data: TensorData::from_shape_fn(
+                (n, c, size.1.max(h), size.0.max(w)),
+                |(n, c, y, x)| match y < h && x < w {
+                    true => self[(n, c, y, x)],
+                    false => self.normal.zero_value(c),
+                },
+            ),
+        }
+    }
+
+    /// `matrix` maps source pixels onto the (w, h) `out_size` output, same as cv2.warpAffine
+    pub fn warp_affine(
+        &self,
+        matrix: &nalgebra::Matrix3<f32>,
+        out_size: (usize, usize),
+        interpolation: Interpolation,
+        border_mode: BorderMode,
+    ) -> crate::Result<Self> {
+        let linear = matrix.fixed_view::<2, 2>(0, 0).determinant();
+        if !linear.is_normal() || matrix.iter().any(|v| !v.is_finite()) {
+            return Err(crate::Error::TransformError(format!(
+                "Degenerate affine matrix: {:?}",
+                matrix.as_slice()
+            )));
+        }
+        let inverse = matrix.try_inverse().ok_or_else(|| {
+            crate::Error::TransformError(format!(
+                "Affine matrix is not invertible: {:?}",
+                matrix.as_slice()
+            ))
+        })?;
+
+        let (n, c, src_y, src_x) = self.dim();
+        let data = ndarray::Zip::from(&mut InputSizeMatrix::from_shape_fn(
+            (n, c, out_size.1, out_size.0),
+            |d| d,
+        ))
+        .par_map_collect(|(n, c, y, x)| {
+            let in_pixel = inverse * nalgebra::Matrix3x1::<f32>::new(*x as f32, *y as f32, 1.);
+            let (x_taps, y_taps) = (
+                interpolation.point_taps(in_pixel.x),
+                interpolation.point_taps(in_pixel.y),
+            );
+
+            y_taps.iter().fold(0., |y_accu, (tap_y, y_weight)| {
+                y_accu
+                    + y_weight
+                        * x_taps.iter().fold(0., |x_accu, (tap_x, x_weight)| {
+                            let v = match (
+                                border_mode.resolve(*tap_y, src_y),
+                                border_mode.resolve(*tap_x, src_x),
+                            ) {
+                                (Some(sy), Some(sx)) => self[(*n, *c, sy, sx)],
+                                _ => self.normal.from_pixel(*c, border_mode.fill_value()),
+                            };
+                            x_accu + x_weight * v
+                        })
+            })
+        });
+
+        Ok(Self {
+            normal: self.normal.clone(),
+            order: self.order,
+            data,
+        })
+    }
+
+    pub fn to_cuda_slice(
+        self,
+        cuda: &std::sync::Arc<cudarc::driver::CudaDevice>,
+    ) -> crate::Result<cudarc::driver::CudaSlice<f32>> {
+        cuda.htod_sync_copy(&self.data.into_raw_vec_and_offset().0)
+            .map_err(crate::Error::CudaError)
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.data.mean().unwrap_or(0.)
+    }
+
+    pub fn norm(&self) -> f32 {
+        self.flatten().map(|v| v * v).sum().sqrt()
+    }
+
+    /// Concatenate tensors along batch, formatted as the first tensor
+    pub fn stack(tensors: Vec<Tensor>) -> crate::Result<Self> {
+        let Some(format) = tensors.first().map(|t| InputFormat {
+            normal: t.normal.clone(),
+            order: t.order,
+        }) else {
+            return Err(crate::Error::InvalidModelIOError(
+                "Unable to stack empty tensor list".into(),
+            ));
+        };
+        let tensors = tensors
+            .into_iter()
+            .map(|mut t| {
+                t.to_format(&format);
+                t.data
+            })
+            .collect::<Vec<TensorData>>();
+
+        Ok(Self {
+            normal: format.normal,
+            order: format.order,
+            data: ndarray::concatenate(
+                ndarray::Axis(0),
+                &tensors.iter().map(|t| t.view()).collect::<Vec<_>>(),
+            )
+            .map_err(crate::Error::as_unknown_error)?,
+        })
+    }
+
+    /// Split batch into (1, c, h, w) tensors
+    pub fn split_batch(&self) -> Vec<Self> {
+        self.axis_chunks_iter(ndarray::Axis(0), 1)
+            .map(|chunk| Self {
+                normal: self.normal.clone(),
+                order: self.order,
+                data: chunk.to_owned(),
+            })
+            .collect()
+    }
+
+    /// (x1, y1, x2, y2) region with exclusive end, clamped to the tensor
+    pub fn roi(&self, rect: (usize, usize, usize, usize)) -> Self {
+        let (_, _, h, w) = self.dim();
+        let (x2, y2) = (rect.2.min(w), rect.3.min(h));
+        let (x1, y1) = (rect.0.min(x2), rect.1.min(y2));
+        Self {
+            normal: self.normal.clone(),
+            order: self.order,
+            data: self.slice(ndarray::s![.., .., y1..y2, x1..x2]).to_owned(),
+        }
+    }
+
+    /// RGBA bytes of a pixel, 1 & 2 channel tensors are read as gray (+ alpha)
+    pub fn pixel_rgba(&self, n: usize, y: usize, x: usize) -> [u8; 4] {
+        let channels = self.dim().1;
+        let v = |c: usize| self.normal.to_pixel(c, self[(n, c, y, x)]) as u8;
+        let (r, b) = match self.order {
+            ChannelOrder::Rgb => (0, 2),
+            ChannelOrder::Bgr => (2, 0),
+        };
+        match channels {
+            0 => [0, 0, 0, u8::MAX],
+            1 => [v(0), v(0), v(0), u8::MAX],
+            2 => [v(0), v(0), v(0), v(1)],
+            3 => [v(r), v(1), v(b), u8::MAX],
+            _ => [v(r), v(1), v(b), v(3)],
+        }
+    }
+
+    pub fn transpose(
+        &mut self,
+        src: Tensor,
+        bbox: (usize, usize, usize, usize),
+    ) -> crate::Result<()> {
+        self.blend(src, bbox, 1.)
+    }
+
+    /// Pastes `src` resized into `bbox`, mixed with the current pixels by 0 ~ 1 `alpha`
+    pub fn blend(
+        &mut self,
+        mut src: Tensor,
+        bbox: (usize, usize, usize, usize),
+        alpha: f32,
+    ) -> crate::Result<()> {
+        let (_, _, tar_y, tar_x) = self.dim();
+        let (_, _, src_y, src_x) = src.dim();
+
+        if self.normal != src.normal || self.order != src.order {
+            src.to_format(&InputFormat {
+                normal: self.normal.clone(),
+                order: self.order,
+            });
+        }
+
+        let (crop_x, crop_y) = (bbox.2.saturating_sub(bbox.0), bbox.3.saturating_sub(bbox.1));
+        if crop_x == 0 || crop_y == 0 {
+            return Err(crate::Error::TransformError(format!(
+                "Empty paste region: {:?}",
+                bbox
+            )));
+        }
+        if src_x != crop_x || src_y != crop_y {
+            src = src.resize(
+                (crop_x, crop_y),
+                Interpolation::for_resize((src_x, src_y), (crop_x, crop_y), Interpolation::Bicubic),
+            );
+        }
+
+        let alpha = alpha.clamp(0., 1.);
+        for ((n, c, y, x), v) in src.indexed_iter() {
+            if (bbox.0 + x) > (tar_x - 1) || (bbox.1 + y) > (tar_y - 1) {
+                continue;
+            }
+            let px = &mut self[(n, c, bbox.1 + y, bbox.0 + x)];
+            *px = *v * alpha + *px * (1. - alpha);
+        }
+
+        Ok(())
+    }
+
+    /// 1px default colored rectangle, see `Annotate` for styled overlays
+    pub fn border(&mut self, bbox: (usize, usize, usize, usize)) -> crate::Result<()> {
+        use super::Annotate;
+        self.draw_bbox(
+            (bbox.0 as f32, bbox.1 as f32, bbox.2 as f32, bbox.3 as f32),
+            super::AnnotationStyle::default().bbox_color,
+            1,
+        );
+        Ok(())
+    }
+}
+
+impl From<TensorData> for Tensor {
+    fn from(value: TensorData) -> Self {
+        Self {
+            normal: Normal::ZeroToP1,
+            order: ChannelOrder::Rgb,
+            data: value,
+        }
+    }
+}
+
+impl From<Tensor> for TensorData {
+    fn from(value: Tensor) -> Self {
+        value.data
+    }
+}
+
+impl From<Tensor> for eframe::egui::ImageData {
+    fn from(value: Tensor) -> Self {
+        use eframe::egui::{Color32, ColorImage, ImageData};
+        use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+        let (_, _, height, width) = value.dim();
+        ImageData::Color(std::sync::Arc::new(ColorImage {
+            size: [width, height],
+            pixels: (0..width * height)
+                .into_par_iter()
+                .map(|i| {
+                    let [r, g, b, a] = value.pixel_rgba(0, i / width, i % width);
+                    Color32::from_rgba_unmultiplied(r, g, b, a)
+                })
+                .collect(),
+        }))
+    }
+}
+
+impl From<Tensor> for crate::image::Image {
+    fn from(mut value: Tensor) -> Self {
+        value.to_normalization(Normal::U8);
+        let (_, _, height, width) = value.dim();
+
+        crate::image::Image::from(image::RgbImage::from_par_fn(
+            width as u32,
+            height as u32,
+            |x, y| {
+                let [r, g, b, _] = value.pixel_rgba(0, y as usize, x as usize);
+                image::Rgb([r, g, b])
+            },
+        ))
+    }
+}
+
+impl std::ops::Deref for Tensor {
+    type Target = TensorData;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl std::ops::DerefMut for Tensor {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::model::TensorData;
+
+    use super::{BorderMode, ChannelOrder, InputFormat, Interpolation, Normal, Tensor};
+    use rand::Rng;
+
+    #[test]
+    fn can_convert_tensor_data_to_image() {
+        let mut rand = rand::thread_rng();
+        let (w, h) = (128, 128);
+        let tensor = Tensor::from(TensorData::from_shape_fn((1, 3, w, h), |_| rand.gen()));
+        let tensor_img = crate::image::Image::from(tensor.clone());
+        let (rand_x, rand_y, rand_c) = (
+            rand.gen_range(0..w),
+            rand.gen_range(0..h),
+            rand.gen_range(0..3),
+        );
+
+        assert_eq!(
+            (tensor[(0, rand_c, rand_y, rand_x)] * 255.) as u8,
+            tensor_img[(rand_x as u32, rand_y as u32)][rand_c],
+        );
+    }
+
+    #[test]
+    fn can_resize_tensor_data() {
+        let mut rand = rand::thread_rng();
+        let test_data = Tensor::default();
+        let new_size = (
+            rand.gen_range(0..1000) as usize,
+            rand.gen_range(0..1000) as usize,
+        );
+
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::Bicubic,
+            Interpolation::Area,
+            Interpolation::Lanczos,
+        ] {
+            let resized_data = test_data.resize(new_size, interpolation);
+            let (_, _, new_y, new_x) = resized_data.dim();
+
+            assert_eq!(new_x, new_size.0, "resized width doesn't match");
+            assert_eq!(new_y, new_size.1, "resized height doesn't match");
+
+            assert_eq!(
+                new_size.0 * new_size.1 * 3,
+                resized_data.flatten().len(),
+                "resized tensor byte length doesn't match"
+            );
+        }
+    }
+
+    #[test]
+    fn can_resize_batched_tensor_data() {
+        let mut rand = rand::thread_rng();
+        for channels in [1, 3, 4] {
+            let test_data = Tensor::from(TensorData::from_shape_fn(
+                (3, channels, 20, 30),
+                |(n, c, _, _)| (n * 10 + c) as f32,
+            ));
+            let new_size = (rand.gen_range(1..100), rand.gen_range(1..100));
+
+            let resized_data = test_data.resize(new_size, Interpolation::Bilinear);
+
+            assert_eq!(resized_data.dim(), (3, channels, new_size.1, new_size.0));
+            for ((n, c, _, _), v) in resized_data.indexed_iter() {
+                assert!((v - (n * 10 + c) as f32).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn can_stack_and_split_batch() {
+        let mut rand = rand::thread_rng();
+        let tensors = (0..4)
+            .map(|_| {
+                Tensor::new(
+                    Normal::ZeroToP1,
+                    TensorData::from_shape_fn((1, 4, 8, 8), |_| rand.gen()),
+                )
+            })
+            .collect::<Vec<Tensor>>();
+
+        let stacked = Tensor::stack(tensors.clone()).expect("Failed to stack tensors");
+        assert_eq!(stacked.dim(), (4, 4, 8, 8));
+
+        for (split, original) in stacked.split_batch().iter().zip(tensors.iter()) {
+            assert_eq!(split.data, original.data);
+        }
+        assert!(Tensor::stack(vec![]).is_err());
+    }
+
+    #[test]
+    fn can_crop_roi() {
+        let test_data = Tensor::new(
+            Normal::U8,
+            TensorData::from_shape_fn((2, 3, 10, 12), |(_, _, y, x)| (y * 12 + x) as f32),
+        );
+
+        let roi = test_data.roi((4, 2, 8, 5));
+        assert_eq!(roi.dim(), (2, 3, 3, 4));
+        assert_eq!(roi[(1, 2, 0, 0)], test_data[(1, 2, 2, 4)]);
+
+        // clamped to the tensor
+        assert_eq!(test_data.roi((10, 8, 20, 20)).dim(), (2, 3, 2, 2));
+    }
+
+    #[test]
+    fn can_blend_into_region() {
+        let mut test_data = Tensor::new(Normal::U8, TensorData::zeros((1, 3, 8, 8)));
+        let src = Tensor::new(Normal::U8, TensorData::from_elem((1, 3, 4, 4), 200.));
+
+        test_data
+            .blend(src, (2, 2, 6, 6), 0.25)
+            .expect("Failed to blend");
+        assert_eq!(test_data[(0, 1, 3, 3)], 50.);
+        assert_eq!(test_data[(0, 1, 1, 1)], 0., "Outside of region untouched");
+    }
+
+    #[test]
+    fn can_draw_border_on_any_channel_count() {
+        for channels in [1, 3, 4] {
+            let mut test_data = Tensor::new(Normal::U8, TensorData::zeros((2, channels, 16, 16)));
+            test_data
+                .border((2, 2, 10, 10))
+                .expect("Failed to draw border");
+
+            for n in 0..2 {
+                assert_eq!(test_data.pixel_rgba(n, 5, 5)[..3], [0, 0, 0]);
+                assert_ne!(test_data.pixel_rgba(n, 2, 5)[..3], [0, 0, 0]);
+                if channels == 4 {
+                    assert_eq!(test_data.pixel_rgba(n, 2, 5)[3], 255);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn can_convert_gray_and_rgba_tensor_to_image_data() {
+        for channels in [1, 4] {
+            let tensor = Tensor::new(Normal::U8, TensorData::from_elem((1, channels, 6, 9), 200.));
+            let img_data = eframe::egui::ImageData::from(tensor);
+            assert_eq!(img_data.size(), [9, 6]);
+        }
+    }
+
+    #[test]
+    fn resize_keeps_constant_tensor_constant() {
+        let mut rand = rand::thread_rng();
+        let value = rand.gen::<f32>();
+        let test_data = Tensor::from(TensorData::from_elem((1, 3, 37, 53), value));
+
+        for interpolation in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::Bicubic,
+            Interpolation::Area,
+            Interpolation::Lanczos,
+        ] {
+            for size in [(20, 11), (120, 90)] {
+                let resized_data = test_data.resize(size, interpolation);
+                assert!(
+                    resized_data.iter().all(|v| (v - value).abs() < 1e-5),
+                    "{:?} changed constant value on resize to {:?}",
+                    interpolation,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_resize_uses_half_pixel_centers() {
+        let test_data = Tensor::from(TensorData::from_shape_fn((1, 3, 1, 2), |(_, _, _, x)| {
+            x as f32
+        }));
+
+        let resized_data = test_data.resize((4, 1), Interpolation::Bilinear);
+
+        for (x, expected) in [0., 0.25, 0.75, 1.].iter().enumerate() {
+            assert_eq!(resized_data[(0, 0, 0, x)], *expected);
+        }
+    }
+
+    #[test]
+    fn area_resize_averages_pixels() {
+        let test_data = Tensor::from(TensorData::from_shape_fn((1, 3, 2, 4), |(_, _, y, x)| {
+            (x + y * 4) as f32
+        }));
+
+        let resized_data = test_data.resize((2, 1), Interpolation::Area);
+
+        assert_eq!(resized_data[(0, 0, 0, 0)], (0. + 1. + 4. + 5.) / 4.);
+        assert_eq!(resized_data[(0, 0, 0, 1)], (2. + 3. + 6. + 7.) / 4.);
+    }
+
+    #[test]
+    fn warp_affine_with_identity_keeps_tensor() {
+        let mut rand = rand::thread_rng();
+        let test_data = Tensor::from(TensorData::from_shape_fn((1, 3, 24, 32), |_| rand.gen()));
+
+        let warped = test_data
+            .warp_affine(
+                &nalgebra::Matrix3::identity(),
+                (32, 24),
+                Interpolation::Bilinear,
+                BorderMode::Replicate,
+            )
+            .expect("Failed to warp tensor");
+
+        assert_eq!(warped.data, test_data.data);
+    }
+
+    #[test]
+    fn warp_affine_applies_border_mode() {
+        let test_data = Tensor::new(
+            Normal::U8,
+            TensorData::from_shape_fn((1, 3, 1, 4), |(_, _, _, x)| x as f32),
+        );
+        // shift right by 2
+        let matrix = nalgebra::Matrix3::new(1., 0., 2., 0., 1., 0., 0., 0., 1.);
+
+        for (border_mode, expected) in [
+            (BorderMode::Constant(9.), [9., 9., 0., 1.]),
+            (BorderMode::Replicate, [0., 0., 0., 1.]),
+            (BorderMode::Reflect, [1., 0., 0., 1.]),
+        ] {
+            let warped = test_data
+                .warp_affine(&matrix, (4, 1), Interpolation::Nearest, border_mode)
+                .expect("Failed to warp tensor");
+            for (x, v) in expected.iter().enumerate() {
+                assert_eq!(warped[(0, 0, 0, x)], *v, "{:?} at x: {}", border_mode, x);
+            }
+        }
+    }
+
+    #[test]
+    fn warp_affine_errors_on_degenerate_matrix() {
+        let test_data = Tensor::default();
+        let matrix = nalgebra::Matrix3::new(0., 0., 5., 0., 0., 5., 0., 0., 1.);
+
+        assert!(test_data
+            .warp_affine(
+                &matrix,
+                (16, 16),
+                Interpolation::Bilinear,
+                BorderMode::default()
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn can_convert_to_per_channel_mean_std() {
+        let mut rand = rand::thread_rng();
+        let pixels = TensorData::from_shape_fn((2, 3, 4, 4), |_| rand.gen_range(0..=255) as f32);
+        let mut t = Tensor::new(Normal::U8, pixels.clone());
+
+        t.to_normalization(Normal::imagenet());
+        let (mean, std) = Normal::imagenet().mean_std(1);
+        assert!((t[(1, 1, 2, 3)] - (pixels[(1, 1, 2, 3)] - mean) / std).abs() < 1e-5);
+
+        t.to_normalization(Normal::N1ToP1);
+        t.to_normalization(Normal::U8);
+        assert!(t
+            .iter()
+            .zip(pixels.iter())
+            .all(|(a, b)| (a - b).abs() < 1e-3));
+    }
+
+    #[test]
+    fn can_convert_channel_order() {
+        let pixels = TensorData::from_shape_fn((1, 3, 2, 2), |(_, c, _, _)| (c * 100) as f32);
+        let mut t = Tensor::new(Normal::U8, pixels).with_order(ChannelOrder::Rgb);
+        let rgba = t.pixel_rgba(0, 1, 1);
+
+        t.to_format(&InputFormat {
+            normal: Normal::imagenet(),
+            order: ChannelOrder::Bgr,
+        });
+        assert_eq!(t.order, ChannelOrder::Bgr);
+        // mean & std are indexed in tensor channel order
+        assert!((t[(0, 0, 0, 0)] - (200. - 123.675) / 58.395).abs() < 1e-5);
+        assert_eq!(t.pixel_rgba(0, 1, 1), rgba);
+    }
+
+    #[test]
+    fn can_convert_tensor_normalization() {
+        let mut rand = rand::thread_rng();
+        let (w, h) = (100, 100);
+        let (rand_x, rand_y, rand_c) = (
+            rand.gen_range(0..w),
+            rand.gen_range(0..h),
+            rand.gen_range(0..3),
+        );
+
+        for mut t in [
+            Tensor::new(
+                Normal::ZeroToP1,
+                TensorData::from_shape_fn((1, 3, h, w), |_| rand.gen()),
+            ),
+            Tensor::new(
+                Normal::N1ToP1,
+                TensorData::from_shape_fn((1, 3, h, w), |_| rand.gen::<f32>() * 2. - 1.),
+            ),
+            Tensor::new(
+                Normal::U8,
+                TensorData::from_shape_fn((1, 3, h, w), |_| rand.gen_range(0..=255) as f32),
+            ),
+        ] {
+            let normalization = t.normal.clone();
+            let t_val = t.data[(0, rand_c, rand_y, rand_x)];
+            if normalization == Normal::ZeroToP1 {
+                t.to_normalization(Normal::N1ToP1);
+                assert_eq!(
+                    t_val * 2. - 1.,
+                    t.data[(0, rand_c, rand_y, rand_x)],
+                    "ZeroToP1 to N1ToP1"
+                );
+                t.to_normalization(normalization.clone());
+                t.to_normalization(Normal::U8);
+                assert_eq!(
+                    t_val * 255.,
+                    t.data[(0, rand_c, rand_y, rand_x)],
+                    "ZeroToP1 to U8"
+                );
+            } else if normalization == Normal::N1ToP1 {
+                t.to_normalization(Normal::ZeroToP1);
+                assert_eq!(
+                    t_val / 2. + 0.5,
+                    t.data[(0, rand_c, rand_y, rand_x)],
+                    "N1ToP1 to ZeroToP1"
+                );
+                t.to_normalization(normalization.clone());
+                t.to_normalization(Normal::U8);
+                assert_eq!(
+                    t_val * 127.5 + 127.5,
+                    t.data[(0, rand_c, rand_y, rand_x)],
+                    "N1ToP1 to U8"
+                );
+            } else {
+                // u8
+                t.to_normalization(Normal::ZeroToP1);
+                assert_eq!(
+                    t_val / 255.,
+                    t.data[(0, rand_c, rand_y, rand_x)],
+                    "U8 to ZeroToP1"
+                );
+                t.to_normalization(normalization.clone());
+                t.to_normalization(Normal::N1ToP1);
+                assert_eq!(
+                    (t_val - 127.5) / 127.5,
+                    t.data[(0, rand_c, rand_y, rand_x)],
+                    "U8 to N1ToP1"
+                );
+            }
+        }
+    }
+}
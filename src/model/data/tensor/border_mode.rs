@@ -0,0 +1,46 @@
+// https://docs.opencv.org/4.x/d2/de8/group__core__array.html#ga209f2f4869e304c82d07739337eae7c5
+
+/// Pixel extrapolation used when sampling outside of a [`super::Tensor`]
+#[derive(Debug, PartialEq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum BorderMode {
+    /// `iiiiii|abcdefgh|iiiiiii` with given 0 ~ 255 pixel value
+    Constant(f32),
+    /// `aaaaaa|abcdefgh|hhhhhhh`
+    Replicate,
+    /// `fedcba|abcdefgh|hgfedcb`
+    Reflect,
+}
+
+impl Default for BorderMode {
+    fn default() -> Self {
+        Self::Constant(0.)
+    }
+}
+
+impl BorderMode {
+    /// In bound index for `idx`, `None` when the constant value should be used instead
+    pub fn resolve(&self, idx: isize, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        if 0 <= idx && idx < len as isize {
+            return Some(idx as usize);
+        }
+        match self {
+            BorderMode::Constant(_) => None,
+            BorderMode::Replicate => Some(idx.clamp(0, len as isize - 1) as usize),
+            BorderMode::Reflect => {
+                let period = 2 * len as isize;
+                let m = idx.rem_euclid(period);
+                Some(if m < len as isize { m } else { period - 1 - m } as usize)
+            }
+        }
+    }
+
+    pub fn fill_value(&self) -> f32 {
+        match self {
+            BorderMode::Constant(v) => *v,
+            _ => 0.,
+        }
+    }
+}
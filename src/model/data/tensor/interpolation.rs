@@ -0,0 +1,126 @@
+// https://docs.opencv.org/4.x/da/d54/group__imgproc__transform.html#ga5bb5a1fea74ea38e1a5445ca803ff121
+
+/// Source index & weight contributing to a single destination index
+pub type Tap = (usize, f32);
+
+// cv2.INTER_CUBIC coefficient
+const CUBIC_A: f32 = -0.75;
+// cv2.INTER_LANCZOS4 window
+const LANCZOS_A: isize = 4;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub enum Interpolation {
+    Nearest,
+    /// Half pixel centered linear (cv2.INTER_LINEAR)
+    #[default]
+    Bilinear,
+    /// Keys cubic convolution (cv2.INTER_CUBIC)
+    Bicubic,
+    /// Pixel area relation, acts as bilinear when upscaling (cv2.INTER_AREA)
+    Area,
+    /// 8x8 lanczos window (cv2.INTER_LANCZOS4)
+    Lanczos,
+}
+
+impl Interpolation {
+    /// Area when shrinking (w, h) `src` into `dst`, otherwise `upscale`
+    pub fn for_resize(src: (usize, usize), dst: (usize, usize), upscale: Self) -> Self {
+        if dst.0 <= src.0 && dst.1 <= src.1 {
+            return Interpolation::Area;
+        }
+        upscale
+    }
+
+    /// Weighted source taps for every destination index along one axis
+    pub fn taps(&self, src_len: usize, dst_len: usize) -> Vec<Vec<Tap>> {
+        if src_len == 0 || dst_len == 0 {
+            return vec![vec![]; dst_len];
+        }
+        let scale = src_len as f32 / dst_len as f32;
+
+        (0..dst_len)
+            .map(|d| match self {
+                Interpolation::Nearest => vec![(
+                    clamp_idx(((d as f32 + 0.5) * scale).floor() as isize, src_len),
+                    1.,
+                )],
+                Interpolation::Area if scale > 1. => area_taps(d, scale, src_len),
+                // half pixel center mapped back to source
+                _ => self
+                    .point_taps((d as f32 + 0.5) * scale - 0.5)
+                    .into_iter()
+                    .map(|(i, w)| (clamp_idx(i, src_len), w))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Weighted source taps around a single source coordinate, indices are not bound checked
+    pub fn point_taps(&self, pos: f32) -> Vec<(isize, f32)> {
+        let taps = match self {
+            Interpolation::Nearest => vec![(pos.round() as isize, 1.)],
+            Interpolation::Bilinear | Interpolation::Area => kernel_taps(pos, 1, triangle),
+            Interpolation::Bicubic => kernel_taps(pos, 2, cubic),
+            Interpolation::Lanczos => kernel_taps(pos, LANCZOS_A, lanczos),
+        };
+        normalize(taps)
+    }
+}
+
+fn clamp_idx(idx: isize, len: usize) -> usize {
+    idx.clamp(0, len as isize - 1) as usize
+}
+
+fn kernel_taps(center: f32, radius: isize, kernel: fn(f32) -> f32) -> Vec<(isize, f32)> {
+    let base = center.floor() as isize;
+    ((base - radius + 1)..=(base + radius))
+        .map(|i| (i, kernel(center - i as f32)))
+        .collect()
+}
+
+fn area_taps(d: usize, scale: f32, len: usize) -> Vec<Tap> {
+    let (start, end) = (d as f32 * scale, (d + 1) as f32 * scale);
+    normalize(
+        ((start.floor() as isize)..(end.ceil() as isize))
+            .map(|i| {
+                let coverage = end.min((i + 1) as f32) - start.max(i as f32);
+                (clamp_idx(i, len), coverage.max(0.))
+            })
+            .collect(),
+    )
+}
+
+fn normalize<I: Copy>(mut taps: Vec<(I, f32)>) -> Vec<(I, f32)> {
+    let sum = taps.iter().fold(0., |accu, (_, w)| accu + w);
+    if sum != 0. {
+        taps.iter_mut().for_each(|(_, w)| *w /= sum);
+    }
+    taps
+}
+
+fn triangle(x: f32) -> f32 {
+    (1. - x.abs()).max(0.)
+}
+
+fn cubic(x: f32) -> f32 {
+    let x = x.abs();
+    if x <= 1. {
+        ((CUBIC_A + 2.) * x - (CUBIC_A + 3.)) * x * x + 1.
+    } else if x < 2. {
+        ((CUBIC_A * x - 5. * CUBIC_A) * x + 8. * CUBIC_A) * x - 4. * CUBIC_A
+    } else {
+        0.
+    }
+}
+
+fn lanczos(x: f32) -> f32 {
+    use std::f32::consts::PI;
+    let a = LANCZOS_A as f32;
+    if x == 0. {
+        return 1.;
+    }
+    if x.abs() >= a {
+        return 0.;
+    }
+    a * (PI * x).sin() * (PI * x / a).sin() / (PI * PI * x * x)
+}
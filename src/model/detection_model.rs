@@ -1,248 +1,347 @@
-use std::collections::HashMap;
-
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
-
-use crate::{Error, Result};
-
-use super::{
-    data::{get_tensor_ref, BBox, Face, KeyPoints, Normal},
-    Tensor,
-};
-
-type AnchorCenters = ndarray::Array<f32, ndarray::Dim<[usize; 2]>>;
-// 640 x 640 | threshold = 0.5 | fmc = 3
-pub struct DetectionModel {
-    session: ort::Session,
-    threshold: f32,
-    // Non Maxium Suppression
-    nms_threshold: f32,
-    input_size: (usize, usize),
-    stride_fpn: Vec<usize>,
-    anchor_map: HashMap<usize, AnchorCenters>,
-}
-
-impl DetectionModel {
-    // det_10g.onnx
-    #[tracing::instrument(name = "Initialize detection model", err)]
-    pub fn new(onnx_path: std::path::PathBuf) -> Result<Self> {
-        let input_size = (640, 640);
-        let stride_fpn = vec![8, 16, 32];
-        let anchor_map =
-            std::sync::Mutex::new(std::collections::HashMap::<usize, AnchorCenters>::new());
-
-        stride_fpn.par_iter().for_each(|stride| {
-            let anchor_centers = ndarray::Array::from_shape_fn(
-                (input_size.0 / stride * input_size.1 / stride * 2, 2),
-                |(idx, a)| {
-                    if a == 0 {
-                        (((idx / 2) * stride) % input_size.1) as f32
-                    } else {
-                        ((((idx / 2) / (input_size.1 / stride)) * stride) % input_size.0) as f32
-                    }
-                },
-            );
-            anchor_map.lock().unwrap().insert(*stride, anchor_centers);
-        });
-
-        Ok(Self {
-            session: super::start_session_from_file(onnx_path)?,
-            // get from config?
-            threshold: 0.5,
-            nms_threshold: 0.4,
-            stride_fpn,
-            input_size,
-            anchor_map: anchor_map.into_inner().map_err(Error::as_guard_error)?,
-        })
-    }
-
-    pub fn run(
-        &mut self,
-        mut tensor: Tensor,
-        cuda_device: Option<&super::ArcCudaDevice>,
-    ) -> Result<Vec<Face>> {
-        // (n, c, h, w)
-        let (_, _, dy, dx) = tensor.dim();
-
-        let (new_w, new_h) = (
-            self.input_size.0,
-            (self.input_size.0 as f32 * dy as f32 / dx as f32) as usize,
-        );
-
-        let det_scale = self.input_size.0 as f32 / dx as f32;
-
-        if dy != new_w || dx != new_h {
-            tensor = tensor.resize((new_w, new_h));
-        }
-
-        tensor.to_normalization(Normal::N1ToP1);
-        if let Some(cuda) = cuda_device {
-            self.run_with_gpu(tensor, cuda, det_scale)
-        } else {
-            self.run_with_cpu(tensor, det_scale)
-        }
-    }
-
-    fn run_with_cpu(&self, tensor: Tensor, det_scale: f32) -> Result<Vec<Face>> {
-        let outputs = self
-            .session
-            .run(ort::inputs![tensor.data].map_err(Error::ModelError)?)
-            .map_err(Error::ModelError)?;
-
-        self.detect(outputs, det_scale)
-    }
-
-    fn run_with_gpu(
-        &self,
-        tensor: Tensor,
-        cuda: &super::ArcCudaDevice,
-        det_scale: f32,
-    ) -> Result<Vec<Face>> {
-        let dim = tensor.dim();
-        let device_data = tensor.to_cuda_slice(cuda)?;
-        let tensor = get_tensor_ref(
-            &device_data,
-            vec![dim.0 as i64, dim.1 as i64, dim.2 as i64, dim.3 as i64],
-        )?;
-
-        let outputs = self
-            .session
-            .run([tensor.into()])
-            .map_err(Error::ModelError)?;
-
-        self.detect(outputs, det_scale)
-    }
-
-    /// stride_fpn (Feature Pyramid Network) | https://jonathan-hui.medium.com/understanding-feature-pyramid-networks-for-object-detection-fpn-45b227b9106c
-    fn detect(&self, outputs: ort::SessionOutputs<'_, '_>, det_scale: f32) -> Result<Vec<Face>> {
-        if outputs.len() != 9 {
-            return Err(Error::InvalidModelIOError(
-                "Detection model output length doesn't match".into(),
-            ));
-        }
-        let fmc = self.stride_fpn.len();
-
-        let mut faces = self
-            .stride_fpn
-            .iter()
-            .enumerate()
-            .flat_map(|(idx, stride)| {
-                let Some(anchor_centers) = self.anchor_map.get(stride) else {
-                    tracing::warn!("Failed to get anchor_centers for stride: {}", stride);
-                    return vec![];
-                };
-
-                let Ok(scores) = &outputs[idx].try_extract_tensor::<f32>() else {
-                    tracing::warn!("Failed to extract scores for stride: {}", stride);
-                    return vec![];
-                };
-
-                let Some(score_slice) = scores.to_slice() else {
-                    tracing::warn!("Failed to get score slice for stride: {}", stride);
-                    return vec![];
-                };
-
-                // border boxes
-                let Ok(bboxes) = &outputs[idx + fmc].try_extract_tensor::<f32>() else {
-                    tracing::warn!("Failed to extract bboxes for stride: {}", stride);
-                    return vec![];
-                };
-                // keypoints
-                let Ok(kpses) = &outputs[idx + fmc * 2].try_extract_tensor::<f32>() else {
-                    tracing::warn!("Failed to extract keypoints for stride: {}", stride);
-                    return vec![];
-                };
-
-                score_slice
-                    .par_iter()
-                    .enumerate()
-                    .filter_map(|(idx, score)| {
-                        if *score < self.threshold {
-                            return None;
-                        }
-                        Some(Face {
-                            score: *score,
-                            bbox: distance2bbox(idx, *stride, det_scale, anchor_centers, bboxes),
-                            keypoints: distance2kps(idx, *stride, det_scale, anchor_centers, kpses),
-                        })
-                    })
-                    .collect()
-            })
-            .collect::<Vec<Face>>();
-
-        faces.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
-                .unwrap_or(std::cmp::Ordering::Equal)
-        });
-
-        Ok(nms(faces, self.nms_threshold))
-    }
-}
-
-fn distance2bbox(
-    idx: usize,
-    stride: usize,
-    det_scale: f32,
-    anchor_centers: &AnchorCenters,
-    // [n, 4]
-    distances: &ndarray::ArrayBase<ndarray::ViewRepr<&f32>, ndarray::Dim<ndarray::IxDynImpl>>,
-) -> BBox {
-    // x1, y1, x2, y2
-    (
-        (anchor_centers[[idx, 0]] - distances[[idx, 0]] * stride as f32) / det_scale,
-        (anchor_centers[[idx, 1]] - distances[[idx, 1]] * stride as f32) / det_scale,
-        (anchor_centers[[idx, 0]] + distances[[idx, 2]] * stride as f32) / det_scale,
-        (anchor_centers[[idx, 1]] + distances[[idx, 3]] * stride as f32) / det_scale,
-    )
-}
-
-fn distance2kps(
-    idx: usize,
-    stride: usize,
-    det_scale: f32,
-    anchor_centers: &AnchorCenters,
-    //[n, 10]
-    distances: &ndarray::ArrayBase<ndarray::ViewRepr<&f32>, ndarray::Dim<ndarray::IxDynImpl>>,
-) -> KeyPoints {
-    // k1, k2, k3, k4, k5
-    KeyPoints([
-        [
-            (anchor_centers[[idx, 0]] + distances[[idx, 0]] * stride as f32) / det_scale,
-            (anchor_centers[[idx, 1]] + distances[[idx, 1]] * stride as f32) / det_scale,
-        ],
-        [
-            (anchor_centers[[idx, 0]] + distances[[idx, 2]] * stride as f32) / det_scale,
-            (anchor_centers[[idx, 1]] + distances[[idx, 3]] * stride as f32) / det_scale,
-        ],
-        [
-            (anchor_centers[[idx, 0]] + distances[[idx, 4]] * stride as f32) / det_scale,
-            (anchor_centers[[idx, 1]] + distances[[idx, 5]] * stride as f32) / det_scale,
-        ],
-        [
-            (anchor_centers[[idx, 0]] + distances[[idx, 6]] * stride as f32) / det_scale,
-            (anchor_centers[[idx, 1]] + distances[[idx, 7]] * stride as f32) / det_scale,
-        ],
-        [
-            (anchor_centers[[idx, 0]] + distances[[idx, 8]] * stride as f32) / det_scale,
-            (anchor_centers[[idx, 1]] + distances[[idx, 9]] * stride as f32) / det_scale,
-        ],
-    ])
-}
-
-// Non Maximum Suppression
-fn nms(faces: Vec<Face>, threshold: f32) -> Vec<Face> {
-    if faces.is_empty() {
-        return faces;
-    }
-
-    let mut filtered: Vec<Face> = vec![faces[0].clone()];
-
-    for face in &faces[1..] {
-        if filtered.iter().any(|f| f.iou(face) > threshold) {
-            continue;
-        }
-        filtered.push(face.clone());
-    }
-
-    filtered
-}
+use std::collections::HashMap;
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{Error, Result};
+
+pub use layout::{DetectorConfig, DetectorLayout};
+pub use suppression::Suppression;
+
+use super::{
+    data::{get_tensor_ref, BBox, Face, InputFormat, Interpolation, KeyPoints},
+    Tensor,
+};
+
+pub mod layout;
+pub mod suppression;
+
+type AnchorCenters = ndarray::Array<f32, ndarray::Dim<[usize; 2]>>;
+// det_10g | 640 x 640 | threshold = 0.5 | fmc = 3
+pub struct DetectionModel {
+    session: ort::Session,
+    threshold: f32,
+    // merging of overlapping detections
+    suppression: Suppression,
+    input_size: (usize, usize),
+    input_format: InputFormat,
+    layout: DetectorLayout,
+    anchor_map: HashMap<usize, AnchorCenters>,
+}
+
+impl DetectionModel {
+    #[tracing::instrument(name = "Initialize detection model", skip(config, suppression), err)]
+    pub fn new(
+        onnx_path: std::path::PathBuf,
+        input_format: InputFormat,
+        config: &DetectorConfig,
+        suppression: Suppression,
+    ) -> Result<Self> {
+        let session = super::start_session_from_file(onnx_path)?;
+        let metadata = session
+            .metadata()
+            .and_then(|m| m.custom(layout::LAYOUT_METADATA_KEY))
+            .unwrap_or_else(|err| {
+                tracing::warn!("Failed to read detection model metadata: {}", err);
+                None
+            });
+        let layout = config
+            .layout
+            .resolve(metadata.as_deref(), session.outputs.len())?;
+        tracing::info!("Detection layout: {:?}", layout);
+
+        // (w, h)
+        let input_size = config.input_size;
+        let anchor_map =
+            std::sync::Mutex::new(std::collections::HashMap::<usize, AnchorCenters>::new());
+
+        if let DetectorLayout::Scrfd {
+            strides, anchors, ..
+        } = &layout
+        {
+            let anchors = (*anchors).max(1);
+            strides.par_iter().for_each(|stride| {
+                let cols = input_size.0 / stride;
+                let anchor_centers = ndarray::Array::from_shape_fn(
+                    (input_size.1 / stride * cols * anchors, 2),
+                    |(idx, a)| {
+                        let location = idx / anchors;
+                        if a == 0 {
+                            ((location % cols) * stride) as f32
+                        } else {
+                            ((location / cols) * stride) as f32
+                        }
+                    },
+                );
+                anchor_map.lock().unwrap().insert(*stride, anchor_centers);
+            });
+        }
+
+        Ok(Self {
+            session,
+            threshold: config.score_threshold,
+            suppression,
+            input_size,
+            input_format,
+            layout,
+            anchor_map: anchor_map.into_inner().map_err(Error::as_guard_error)?,
+        })
+    }
+
+    pub fn run(
+        &mut self,
+        tensor: Tensor,
+        cuda_device: Option<&super::ArcCudaDevice>,
+    ) -> Result<Vec<Face>> {
+        let (mut tensor, det_scale) = letterbox(tensor, self.input_size);
+
+        tensor.to_format(&self.input_format);
+        if let Some(cuda) = cuda_device {
+            self.run_with_gpu(tensor, cuda, det_scale)
+        } else {
+            self.run_with_cpu(tensor, det_scale)
+        }
+    }
+
+    fn run_with_cpu(&self, tensor: Tensor, det_scale: f32) -> Result<Vec<Face>> {
+        let outputs = self
+            .session
+            .run(ort::inputs![tensor.data].map_err(Error::ModelError)?)
+            .map_err(Error::ModelError)?;
+
+        self.detect(outputs, det_scale)
+    }
+
+    fn run_with_gpu(
+        &self,
+        tensor: Tensor,
+        cuda: &super::ArcCudaDevice,
+        det_scale: f32,
+    ) -> Result<Vec<Face>> {
+        let dim = tensor.dim();
+        let device_data = tensor.to_cuda_slice(cuda)?;
+        let tensor = get_tensor_ref(
+            &device_data,
+            vec![dim.0 as i64, dim.1 as i64, dim.2 as i64, dim.3 as i64],
+        )?;
+
+        let outputs = self
+            .session
+            .run([tensor.into()])
+            .map_err(Error::ModelError)?;
+
+        self.detect(outputs, det_scale)
+    }
+
+    fn detect(&self, outputs: ort::SessionOutputs<'_, '_>, det_scale: f32) -> Result<Vec<Face>> {
+        let faces = match &self.layout {
+            DetectorLayout::Scrfd {
+                strides, keypoints, ..
+            } => self.detect_scrfd(&outputs, strides, *keypoints, det_scale)?,
+            DetectorLayout::Yolo => layout::decode_yolo(
+                &outputs[0]
+                    .try_extract_tensor::<f32>()
+                    .map_err(Error::ModelError)?,
+                self.threshold,
+                det_scale,
+            )?,
+            DetectorLayout::Auto => {
+                return Err(Error::InvalidModelIOError(
+                    "Unresolved detection layout".into(),
+                ))
+            }
+        };
+
+        Ok(self.suppression.apply(faces))
+    }
+
+    /// stride_fpn (Feature Pyramid Network) | https://jonathan-hui.medium.com/understanding-feature-pyramid-networks-for-object-detection-fpn-45b227b9106c
+    fn detect_scrfd(
+        &self,
+        outputs: &ort::SessionOutputs<'_, '_>,
+        strides: &[usize],
+        keypoints: bool,
+        det_scale: f32,
+    ) -> Result<Vec<Face>> {
+        let fmc = strides.len();
+        if outputs.len() != fmc * if keypoints { 3 } else { 2 } {
+            return Err(Error::InvalidModelIOError(
+                "Detection model output length doesn't match".into(),
+            ));
+        }
+
+        Ok(strides
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, stride)| {
+                let Some(anchor_centers) = self.anchor_map.get(stride) else {
+                    tracing::warn!("Failed to get anchor_centers for stride: {}", stride);
+                    return vec![];
+                };
+
+                let Ok(scores) = &outputs[idx].try_extract_tensor::<f32>() else {
+                    tracing::warn!("Failed to extract scores for stride: {}", stride);
+                    return vec![];
+                };
+
+                let Some(score_slice) = scores.to_slice() else {
+                    tracing::warn!("Failed to get score slice for stride: {}", stride);
+                    return vec![];
+                };
+
+                // output shaped for another input size would index past the anchors
+                if score_slice.len() > anchor_centers.nrows() {
+                    tracing::warn!(
+                        "Stride {} has {} scores for {} anchors, check detector.input_size",
+                        stride,
+                        score_slice.len(),
+                        anchor_centers.nrows()
+                    );
+                    return vec![];
+                }
+
+                // border boxes
+                let Ok(bboxes) = &outputs[idx + fmc].try_extract_tensor::<f32>() else {
+                    tracing::warn!("Failed to extract bboxes for stride: {}", stride);
+                    return vec![];
+                };
+                // keypoints, estimated from the bbox when the model has none
+                let kpses = match keypoints {
+                    true => match outputs[idx + fmc * 2].try_extract_tensor::<f32>() {
+                        Ok(kpses) => Some(kpses),
+                        Err(_) => {
+                            tracing::warn!("Failed to extract keypoints for stride: {}", stride);
+                            return vec![];
+                        }
+                    },
+                    false => None,
+                };
+
+                score_slice
+                    .par_iter()
+                    .enumerate()
+                    .filter_map(|(idx, score)| {
+                        if *score < self.threshold {
+                            return None;
+                        }
+                        let bbox = distance2bbox(idx, *stride, det_scale, anchor_centers, bboxes);
+                        let keypoints = match &kpses {
+                            Some(kpses) => {
+                                distance2kps(idx, *stride, det_scale, anchor_centers, kpses)
+                            }
+                            None => KeyPoints::from_bbox(&bbox),
+                        };
+                        Some(Face {
+                            score: *score,
+                            bbox,
+                            keypoints,
+                        })
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+/// Fits the frame inside the (w, h) `input_size` keeping its aspect, padding the rest.
+/// Anchors & fixed size exports expect exactly `input_size`, returns the scale to map detections back
+fn letterbox(mut tensor: Tensor, input_size: (usize, usize)) -> (Tensor, f32) {
+    // (n, c, h, w)
+    let (_, _, dy, dx) = tensor.dim();
+    let det_scale =
+        (input_size.0 as f32 / dx.max(1) as f32).min(input_size.1 as f32 / dy.max(1) as f32);
+    let (new_w, new_h) = (
+        ((dx as f32 * det_scale).round() as usize).clamp(1, input_size.0),
+        ((dy as f32 * det_scale).round() as usize).clamp(1, input_size.1),
+    );
+
+    if (dx, dy) != (new_w, new_h) {
+        tensor = tensor.resize(
+            (new_w, new_h),
+            Interpolation::for_resize((dx, dy), (new_w, new_h), Interpolation::Bilinear),
+        );
+    }
+    if (new_w, new_h) != input_size {
+        tensor = tensor.pad(input_size);
+    }
+    (tensor, det_scale)
+}
+
+fn distance2bbox(
+    idx: usize,
+    stride: usize,
+    det_scale: f32,
+    anchor_centers: &AnchorCenters,
+    // [n, 4]
+    distances: &ndarray::ArrayBase<ndarray::ViewRepr<&f32>, ndarray::Dim<ndarray::IxDynImpl>>,
+) -> BBox {
+    // x1, y1, x2, y2
+    (
+        (anchor_centers[[idx, 0]] - distances[[idx, 0]] * stride as f32) / det_scale,
+        (anchor_centers[[idx, 1]] - distances[[idx, 1]] * stride as f32) / det_scale,
+        (anchor_centers[[idx, 0]] + distances[[idx, 2]] * stride as f32) / det_scale,
+        (anchor_centers[[idx, 1]] + distances[[idx, 3]] * stride as f32) / det_scale,
+    )
+}
+
+fn distance2kps(
+    idx: usize,
+    stride: usize,
+    det_scale: f32,
+    anchor_centers: &AnchorCenters,
+    //[n, 10]
+    distances: &ndarray::ArrayBase<ndarray::ViewRepr<&f32>, ndarray::Dim<ndarray::IxDynImpl>>,
+) -> KeyPoints {
+    // k1, k2, k3, k4, k5
+    KeyPoints([
+        [
+            (anchor_centers[[idx, 0]] + distances[[idx, 0]] * stride as f32) / det_scale,
+            (anchor_centers[[idx, 1]] + distances[[idx, 1]] * stride as f32) / det_scale,
+        ],
+        [
+            (anchor_centers[[idx, 0]] + distances[[idx, 2]] * stride as f32) / det_scale,
+            (anchor_centers[[idx, 1]] + distances[[idx, 3]] * stride as f32) / det_scale,
+        ],
+        [
+            (anchor_centers[[idx, 0]] + distances[[idx, 4]] * stride as f32) / det_scale,
+            (anchor_centers[[idx, 1]] + distances[[idx, 5]] * stride as f32) / det_scale,
+        ],
+        [
+            (anchor_centers[[idx, 0]] + distances[[idx, 6]] * stride as f32) / det_scale,
+            (anchor_centers[[idx, 1]] + distances[[idx, 7]] * stride as f32) / det_scale,
+        ],
+        [
+            (anchor_centers[[idx, 0]] + distances[[idx, 8]] * stride as f32) / det_scale,
+            (anchor_centers[[idx, 1]] + distances[[idx, 9]] * stride as f32) / det_scale,
+        ],
+    ])
+}
+
+#[cfg(test)]
+mod test {
+    use super::letterbox;
+    use crate::model::{Tensor, TensorData};
+
+    #[test]
+    fn letterboxes_portrait_frames_to_input_size() {
+        // 480 x 640 portrait, (n, c, h, w)
+        let frame = Tensor::from(TensorData::from_elem((1, 3, 640, 480), 1.));
+        let (input, det_scale) = letterbox(frame, (640, 640));
+        assert_eq!(input.dim(), (1, 3, 640, 640));
+        assert_eq!(det_scale, 1.);
+        assert_eq!(input[(0, 0, 320, 479)], 1.);
+        assert_eq!(input[(0, 0, 320, 480)], input.normal.zero_value(0));
+
+        // 1080 x 1920 is scaled by a third, then padded on the right
+        let frame = Tensor::from(TensorData::from_elem((1, 3, 1920, 1080), 1.));
+        let (input, det_scale) = letterbox(frame, (640, 640));
+        assert_eq!(input.dim(), (1, 3, 640, 640));
+        assert!((det_scale - 1. / 3.).abs() < 1e-6);
+        assert!((input[(0, 1, 639, 359)] - 1.).abs() < 1e-4);
+        assert_eq!(input[(0, 1, 639, 360)], input.normal.zero_value(1));
+
+        // landscape pads the bottom of a non square input
+        let frame = Tensor::from(TensorData::from_elem((1, 3, 360, 640), 1.));
+        let (input, _) = letterbox(frame, (320, 256));
+        assert_eq!(input.dim(), (1, 3, 256, 320));
+    }
+}
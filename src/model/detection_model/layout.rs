@@ -0,0 +1,299 @@
+// Output layouts of supported face detectors
+// SCRFD | https://github.com/deepinsight/insightface/blob/master/python-package/insightface/model_zoo/scrfd.py
+// YOLO face | https://github.com/deepcam-cn/yolov5-face | https://github.com/akanametov/yolo-face
+
+use crate::{
+    model::data::{BBox, Face, KeyPoints},
+    Error, Result,
+};
+
+/// Model metadata key overriding the guessed layout, "scrfd" | "yolo" | json of `DetectorLayout`
+pub const LAYOUT_METADATA_KEY: &str = "layout";
+// xywh + score | objectness & class | keypoints
+const YOLO_CHANNELS: [usize; 4] = [5, 6, 16, 20];
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct DetectorConfig {
+    /// Onnx file in the models directory
+    pub file: String,
+    /// (w, h) the frame is resized to
+    pub input_size: (usize, usize),
+    pub layout: DetectorLayout,
+    /// Minimum face score
+    pub score_threshold: f32,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            file: "det_10g.onnx".into(),
+            input_size: (640, 640),
+            layout: DetectorLayout::Auto,
+            score_threshold: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum DetectorLayout {
+    /// From model metadata, otherwise guessed by output count
+    Auto,
+    /// Per stride score | bbox | (keypoints) outputs
+    Scrfd {
+        strides: Vec<usize>,
+        /// Anchors per feature map location
+        anchors: usize,
+        keypoints: bool,
+    },
+    /// Single (1, n, c) or (1, c, n) output of center xywh boxes
+    Yolo,
+}
+
+impl DetectorLayout {
+    fn scrfd(strides: usize, keypoints: bool) -> Self {
+        match strides {
+            5 => DetectorLayout::Scrfd {
+                strides: vec![8, 16, 32, 64, 128],
+                anchors: 1,
+                keypoints,
+            },
+            _ => DetectorLayout::Scrfd {
+                strides: vec![8, 16, 32],
+                anchors: 2,
+                keypoints,
+            },
+        }
+    }
+
+    /// Concrete layout from config, `metadata` layout value & the model's output count
+    pub fn resolve(&self, metadata: Option<&str>, output_count: usize) -> Result<Self> {
+        let layout = match self {
+            DetectorLayout::Auto => match metadata.map(str::trim) {
+                Some(value) if value.eq_ignore_ascii_case("yolo") => DetectorLayout::Yolo,
+                Some(value) if value.eq_ignore_ascii_case("scrfd") => {
+                    Self::from_output_count(output_count)
+                        .filter(|l| l != &DetectorLayout::Yolo)
+                        .ok_or_else(|| Self::count_error(output_count))?
+                }
+                Some(value) => serde_json::from_str::<DetectorLayout>(value).map_err(|err| {
+                    Error::InvalidModelIOError(format!(
+                        "Unknown detector layout in model metadata {:?}: {}",
+                        value, err
+                    ))
+                })?,
+                None => Self::from_output_count(output_count)
+                    .ok_or_else(|| Self::count_error(output_count))?,
+            },
+            layout => layout.clone(),
+        };
+        if layout == DetectorLayout::Auto {
+            return Err(Error::InvalidModelIOError(
+                "Detector layout resolved to auto".into(),
+            ));
+        }
+
+        let expected = layout.output_count();
+        if expected != output_count {
+            return Err(Error::InvalidModelIOError(format!(
+                "Detector layout {:?} expects {} outputs, model has {}",
+                layout, expected, output_count
+            )));
+        }
+        Ok(layout)
+    }
+
+    fn from_output_count(count: usize) -> Option<Self> {
+        match count {
+            1 => Some(DetectorLayout::Yolo),
+            6 => Some(Self::scrfd(3, false)),
+            9 => Some(Self::scrfd(3, true)),
+            10 => Some(Self::scrfd(5, false)),
+            15 => Some(Self::scrfd(5, true)),
+            _ => None,
+        }
+    }
+
+    fn count_error(count: usize) -> Error {
+        Error::InvalidModelIOError(format!(
+            "Can't infer detector layout from {} outputs, set it in config",
+            count
+        ))
+    }
+
+    fn output_count(&self) -> usize {
+        match self {
+            DetectorLayout::Auto => 0,
+            DetectorLayout::Scrfd {
+                strides, keypoints, ..
+            } => strides.len() * if *keypoints { 3 } else { 2 },
+            DetectorLayout::Yolo => 1,
+        }
+    }
+}
+
+/// Faces of a fused yolo output, in model input pixels divided by `det_scale`
+pub fn decode_yolo(
+    output: &ndarray::ArrayViewD<'_, f32>,
+    threshold: f32,
+    det_scale: f32,
+) -> Result<Vec<Face>> {
+    let shape = output.shape().to_vec();
+    let output = match shape.as_slice() {
+        [1, a, b] => output.to_shape((*a, *b)),
+        [a, b] => output.to_shape((*a, *b)),
+        _ => {
+            return Err(Error::InvalidModelIOError(format!(
+                "Unexpected yolo output shape {:?}",
+                shape
+            )))
+        }
+    }
+    .map_err(Error::as_unknown_error)?;
+    // (candidates, channels)
+    let output =
+        if YOLO_CHANNELS.contains(&output.nrows()) && !YOLO_CHANNELS.contains(&output.ncols()) {
+            output.t().to_owned()
+        } else {
+            output.to_owned()
+        };
+
+    let channels = output.ncols();
+    if !YOLO_CHANNELS.contains(&channels) {
+        return Err(Error::InvalidModelIOError(format!(
+            "Unsupported yolo output channels: {}",
+            channels
+        )));
+    }
+
+    let faces = output
+        .rows()
+        .into_iter()
+        .filter_map(|row| {
+            let (score, keypoints): (f32, Option<KeyPoints>) = match channels {
+                // xywh | score
+                5 => (row[4], None),
+                // xywh | objectness | class
+                6 => (row[4] * row[5], None),
+                // xywh | objectness | 5 * xy | class (yolov5-face)
+                16 => (
+                    row[4] * row[15],
+                    Some(KeyPoints(std::array::from_fn(|k| {
+                        [row[5 + k * 2], row[6 + k * 2]]
+                    }))),
+                ),
+                // xywh | score | 5 * xy visibility (yolov8-face)
+                20 => (
+                    row[4],
+                    Some(KeyPoints(std::array::from_fn(|k| {
+                        [row[5 + k * 3], row[6 + k * 3]]
+                    }))),
+                ),
+                _ => return None,
+            };
+            if score < threshold {
+                return None;
+            }
+
+            let (cx, cy, w, h) = (row[0], row[1], row[2], row[3]);
+            let bbox: BBox = (
+                (cx - w / 2.) / det_scale,
+                (cy - h / 2.) / det_scale,
+                (cx + w / 2.) / det_scale,
+                (cy + h / 2.) / det_scale,
+            );
+            let keypoints = match keypoints {
+                Some(kps) => KeyPoints(kps.0.map(|[x, y]| [x / det_scale, y / det_scale])),
+                None => KeyPoints::from_bbox(&bbox),
+            };
+            Some(Face {
+                score,
+                keypoints,
+                bbox,
+            })
+        })
+        .collect();
+
+    Ok(faces)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_yolo, DetectorLayout};
+
+    #[test]
+    fn infers_layout_from_outputs_and_metadata() {
+        let auto = DetectorLayout::Auto;
+        assert_eq!(
+            auto.resolve(None, 9).expect("Failed to resolve 9 outputs"),
+            DetectorLayout::Scrfd {
+                strides: vec![8, 16, 32],
+                anchors: 2,
+                keypoints: true
+            }
+        );
+        assert_eq!(
+            auto.resolve(None, 10)
+                .expect("Failed to resolve 10 outputs"),
+            DetectorLayout::Scrfd {
+                strides: vec![8, 16, 32, 64, 128],
+                anchors: 1,
+                keypoints: false
+            }
+        );
+        assert_eq!(
+            auto.resolve(Some("YOLO"), 1)
+                .expect("Failed to resolve metadata"),
+            DetectorLayout::Yolo
+        );
+        assert_eq!(
+            auto.resolve(
+                Some(r#"{"Scrfd":{"strides":[16,32],"anchors":1,"keypoints":false}}"#),
+                4
+            )
+            .expect("Failed to resolve json metadata"),
+            DetectorLayout::Scrfd {
+                strides: vec![16, 32],
+                anchors: 1,
+                keypoints: false
+            }
+        );
+
+        assert!(auto.resolve(None, 7).is_err(), "Unknown output count");
+        assert!(
+            DetectorLayout::Yolo.resolve(None, 9).is_err(),
+            "Configured layout should match the model"
+        );
+    }
+
+    #[test]
+    fn decodes_channel_first_yolo_output() {
+        // (1, 20, 3) yolov8-face, one candidate above the threshold
+        let mut output = ndarray::Array3::<f32>::zeros((1, 20, 3));
+        for (c, v) in [100., 60., 40., 80., 0.9].into_iter().enumerate() {
+            output[(0, c, 1)] = v;
+        }
+        for k in 0..5 {
+            output[(0, 5 + k * 3, 1)] = 90. + k as f32;
+            output[(0, 6 + k * 3, 1)] = 50.;
+        }
+        output[(0, 4, 2)] = 0.2;
+
+        let faces = decode_yolo(&output.into_dyn().view(), 0.5, 2.).expect("Failed to decode");
+        assert_eq!(faces.len(), 1);
+        assert_eq!(faces[0].bbox, (40., 10., 60., 50.));
+        assert_eq!(faces[0].keypoints[2], [46., 25.]);
+    }
+
+    #[test]
+    fn estimates_keypoints_without_landmarks() {
+        // (1, 2, 5) candidates first, boxes only
+        let output = ndarray::arr3(&[[[50., 50., 20., 20., 0.8], [10., 10., 4., 4., 0.1]]]);
+        let faces = decode_yolo(&output.into_dyn().view(), 0.5, 1.).expect("Failed to decode");
+        assert_eq!(faces.len(), 1);
+        assert!(faces[0]
+            .keypoints
+            .iter()
+            .all(|[x, y]| (40. ..=60.).contains(x) && (40. ..=60.).contains(y)));
+    }
+}
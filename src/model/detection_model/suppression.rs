@@ -0,0 +1,221 @@
+// Merging of overlapping detections
+// Soft-NMS | https://arxiv.org/abs/1704.04503
+// Weighted Boxes Fusion | https://arxiv.org/abs/1910.13302
+
+use crate::model::data::{BBox, Face, KeyPoints};
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Suppression {
+    /// Greedy Non Maximum Suppression, drops boxes overlapping a better one
+    Hard { iou_threshold: f32 },
+    /// Scores of overlapping boxes decay by (1 - IoU) past the threshold
+    SoftLinear {
+        iou_threshold: f32,
+        /// Decayed boxes below this score are dropped
+        score_threshold: f32,
+    },
+    /// Scores of overlapping boxes decay by exp(-IoU^2 / sigma)
+    SoftGaussian { sigma: f32, score_threshold: f32 },
+    /// Overlapping boxes & keypoints are averaged, weighted by score
+    WeightedFusion { iou_threshold: f32 },
+}
+
+impl Default for Suppression {
+    fn default() -> Self {
+        Suppression::Hard { iou_threshold: 0.4 }
+    }
+}
+
+impl Suppression {
+    /// Remaining faces sorted by score
+    pub fn apply(&self, mut faces: Vec<Face>) -> Vec<Face> {
+        faces.sort_by(|a, b| b.score.total_cmp(&a.score));
+        match self {
+            Suppression::Hard { iou_threshold } => nms(faces, *iou_threshold),
+            Suppression::SoftLinear {
+                iou_threshold,
+                score_threshold,
+            } => soft_nms(faces, *score_threshold, |iou| {
+                if iou > *iou_threshold {
+                    1. - iou
+                } else {
+                    1.
+                }
+            }),
+            Suppression::SoftGaussian {
+                sigma,
+                score_threshold,
+            } => {
+                let sigma = sigma.max(f32::EPSILON);
+                soft_nms(faces, *score_threshold, |iou| (-iou * iou / sigma).exp())
+            }
+            Suppression::WeightedFusion { iou_threshold } => wbf(faces, *iou_threshold),
+        }
+    }
+}
+
+// Non Maximum Suppression, `faces` sorted by score
+fn nms(faces: Vec<Face>, threshold: f32) -> Vec<Face> {
+    let mut filtered: Vec<Face> = vec![];
+
+    for face in faces {
+        if filtered.iter().any(|f| f.iou(&face) > threshold) {
+            continue;
+        }
+        filtered.push(face);
+    }
+
+    filtered
+}
+
+// `decay` of the remaining scores by IoU with the picked face
+fn soft_nms(mut faces: Vec<Face>, score_threshold: f32, decay: impl Fn(f32) -> f32) -> Vec<Face> {
+    let mut kept: Vec<Face> = vec![];
+
+    while !faces.is_empty() {
+        let best = faces
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.score.total_cmp(&b.1.score))
+            .map(|(idx, _)| idx)
+            .unwrap_or_default();
+        let picked = faces.swap_remove(best);
+
+        faces
+            .iter_mut()
+            .for_each(|f| f.score *= decay(picked.iou(f)));
+        faces.retain(|f| f.score >= score_threshold);
+        kept.push(picked);
+    }
+
+    kept
+}
+
+// `faces` sorted by score, clusters are matched against their fused box
+fn wbf(faces: Vec<Face>, threshold: f32) -> Vec<Face> {
+    // (fused, members)
+    let mut clusters: Vec<(Face, Vec<Face>)> = vec![];
+
+    for face in faces {
+        let matched = clusters
+            .iter()
+            .enumerate()
+            .map(|(idx, (fused, _))| (idx, fused.iou(&face)))
+            .filter(|(_, iou)| *iou > threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(idx, _)| idx);
+
+        match matched {
+            Some(idx) => {
+                let (fused, members) = &mut clusters[idx];
+                members.push(face);
+                *fused = fuse(members);
+            }
+            None => clusters.push((face.clone(), vec![face])),
+        }
+    }
+
+    let mut fused = clusters
+        .into_iter()
+        .map(|(fused, _)| fused)
+        .collect::<Vec<Face>>();
+    fused.sort_by(|a, b| b.score.total_cmp(&a.score));
+    fused
+}
+
+// score weighted average, mean score
+fn fuse(members: &[Face]) -> Face {
+    let total = members.iter().map(|f| f.score).sum::<f32>();
+    if total <= 0. || !total.is_finite() {
+        return members[0].clone();
+    }
+
+    let weighted =
+        |value: fn(&Face) -> f32| members.iter().map(|f| value(f) * f.score).sum::<f32>() / total;
+    let bbox: BBox = (
+        weighted(|f| f.bbox.0),
+        weighted(|f| f.bbox.1),
+        weighted(|f| f.bbox.2),
+        weighted(|f| f.bbox.3),
+    );
+
+    let mut keypoints = KeyPoints([[0.; 2]; 5]);
+    for face in members {
+        for (kp, [x, y]) in keypoints.iter_mut().zip(face.keypoints.iter()) {
+            kp[0] += x * face.score / total;
+            kp[1] += y * face.score / total;
+        }
+    }
+
+    Face {
+        score: total / members.len() as f32,
+        keypoints,
+        bbox,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Suppression;
+    use crate::model::data::{Face, KeyPoints};
+
+    fn face_at(x: f32, score: f32) -> Face {
+        Face {
+            score,
+            bbox: (x, 0., x + 100., 100.),
+            keypoints: KeyPoints([[x + 30., 40.]; 5]),
+        }
+    }
+
+    // two overlapping faces (IoU ~0.6) & a duplicate of the first one
+    fn crowd() -> Vec<Face> {
+        vec![face_at(25., 0.8), face_at(0., 0.9), face_at(2., 0.7)]
+    }
+
+    #[test]
+    fn hard_nms_drops_overlapping_faces() {
+        let faces = Suppression::Hard { iou_threshold: 0.4 }.apply(crowd());
+        assert_eq!(faces.len(), 1, "Crowded face should be suppressed");
+        assert_eq!(faces[0].score, 0.9);
+    }
+
+    #[test]
+    fn soft_nms_keeps_crowded_faces() {
+        for suppression in [
+            Suppression::SoftLinear {
+                iou_threshold: 0.4,
+                score_threshold: 0.25,
+            },
+            Suppression::SoftGaussian {
+                sigma: 0.5,
+                score_threshold: 0.25,
+            },
+        ] {
+            let faces = suppression.apply(crowd());
+            assert_eq!(faces.len(), 2, "{:?} kept {:?}", suppression, faces);
+            assert_eq!(faces[0].score, 0.9);
+            assert!(
+                faces[1].bbox.0 == 25. && faces[1].score < 0.8,
+                "{:?} should keep the neighbour with a decayed score, got {:?}",
+                suppression,
+                faces[1]
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_fusion_averages_boxes_and_keypoints() {
+        let faces = Suppression::WeightedFusion { iou_threshold: 0.7 }.apply(vec![
+            face_at(0., 0.6),
+            face_at(10., 0.2),
+            face_at(300., 0.3),
+        ]);
+        assert_eq!(faces.len(), 2);
+
+        let fused = &faces[0];
+        assert!((fused.bbox.0 - 2.5).abs() < 1e-4, "Got {:?}", fused.bbox);
+        assert!((fused.keypoints[4][0] - 32.5).abs() < 1e-4);
+        assert!((fused.score - 0.4).abs() < 1e-4);
+        assert_eq!(faces[1].bbox.0, 300., "Distant face left as is");
+    }
+}
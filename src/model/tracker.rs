@@ -0,0 +1,331 @@
+pub use filter::{FaceFilter, Smoothing};
+pub use propagation::{DetectionSchedule, DetectionStats, Propagation, Propagator};
+
+use super::data::{Face, VectorizedTensor};
+
+pub mod filter;
+pub mod propagation;
+
+/// L2 normalized recognition embedding
+pub type Embedding = ndarray::Array1<f32>;
+
+// running average weight of the previous embedding
+const EMBEDDING_MOMENTUM: f32 = 0.9;
+const DEFAULT_FRAME_RATE: f32 = 30.;
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TrackerConfig {
+    /// Minimum IoU for a detection to continue a track
+    pub iou_threshold: f32,
+    /// Match by recognition embedding as well, costs a recognition pass per face
+    pub use_embedding: bool,
+    /// Minimum cosine similarity for an embedding only match
+    pub embedding_threshold: f32,
+    /// 0 ~ 1 share of the embedding similarity in the match score
+    pub embedding_weight: f32,
+    /// Frames a track survives without a matching detection
+    pub max_misses: usize,
+    /// Temporal filter of tracked bboxes & keypoints
+    pub smoothing: Smoothing,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            iou_threshold: 0.3,
+            use_embedding: false,
+            embedding_threshold: 0.5,
+            embedding_weight: 0.5,
+            max_misses: 15,
+            smoothing: Smoothing::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub id: usize,
+    /// Latest matched detection, smoothed
+    pub face: Face,
+    /// Frames matched since creation
+    pub hits: usize,
+    /// Consecutive frames without a match
+    pub misses: usize,
+    embedding: Option<Embedding>,
+    filter: FaceFilter,
+}
+
+impl Track {
+    pub fn embedding(&self) -> Option<&Embedding> {
+        self.embedding.as_ref()
+    }
+
+    fn update_embedding(&mut self, embedding: Option<Embedding>) {
+        let Some(embedding) = embedding else {
+            return;
+        };
+        self.embedding = Some(match self.embedding.take() {
+            Some(prev) => {
+                normalize(prev * EMBEDDING_MOMENTUM + embedding * (1. - EMBEDDING_MOMENTUM))
+            }
+            None => embedding,
+        });
+    }
+}
+
+/// Assigns persistent ids to detections across frames
+#[derive(Debug)]
+pub struct Tracker {
+    config: TrackerConfig,
+    tracks: Vec<Track>,
+    next_id: usize,
+    // seconds between updates
+    frame_interval: f32,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self::new(TrackerConfig::default())
+    }
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        Self {
+            config,
+            tracks: vec![],
+            next_id: 0,
+            frame_interval: 1. / DEFAULT_FRAME_RATE,
+        }
+    }
+
+    /// Rate `update` is called at, drives smoothing time constants
+    pub fn set_frame_rate(&mut self, fps: f32) {
+        if fps.is_normal() && fps > 0. {
+            self.frame_interval = 1. / fps;
+        }
+    }
+
+    pub fn config(&self) -> &TrackerConfig {
+        &self.config
+    }
+
+    /// Live tracks, including ones missed in the latest frame
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Track> {
+        self.tracks.iter().find(|t| t.id == id)
+    }
+
+    pub fn reset(&mut self) {
+        self.tracks.clear();
+    }
+
+    /// IoU only matching, returns track id of each face
+    pub fn update(&mut self, faces: &[Face]) -> Vec<usize> {
+        self.update_with_embeddings(faces, &vec![None; faces.len()])
+    }
+
+    /// `embeddings` are raw recognition outputs aligned with `faces`, returns track id of each face
+    pub fn update_with_embeddings(
+        &mut self,
+        faces: &[Face],
+        embeddings: &[Option<&VectorizedTensor>],
+    ) -> Vec<usize> {
+        let embeddings = (0..faces.len())
+            .map(|idx| {
+                embeddings
+                    .get(idx)
+                    .copied()
+                    .flatten()
+                    .map(|e| normalize(e.0.flatten().to_owned()))
+            })
+            .collect::<Vec<Option<Embedding>>>();
+
+        // (score, track idx, face idx), greedily matched from the best score
+        let tracker = &*self;
+        let mut candidates = tracker
+            .tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(track_idx, track)| {
+                faces.iter().zip(embeddings.iter()).enumerate().filter_map(
+                    move |(face_idx, (face, embedding))| {
+                        tracker
+                            .match_score(track, face, embedding.as_ref())
+                            .map(|score| (score, track_idx, face_idx))
+                    },
+                )
+            })
+            .collect::<Vec<(f32, usize, usize)>>();
+        candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut face_tracks: Vec<Option<usize>> = vec![None; faces.len()];
+        let mut matched_tracks = vec![false; self.tracks.len()];
+        for (_, track_idx, face_idx) in candidates {
+            if matched_tracks[track_idx] || face_tracks[face_idx].is_some() {
+                continue;
+            }
+            matched_tracks[track_idx] = true;
+            face_tracks[face_idx] = Some(track_idx);
+        }
+
+        self.tracks
+            .iter_mut()
+            .zip(matched_tracks.iter())
+            .filter(|(_, matched)| !**matched)
+            .for_each(|(track, _)| track.misses += 1);
+
+        let frame_interval = self.frame_interval;
+        let ids = faces
+            .iter()
+            .zip(embeddings)
+            .zip(face_tracks)
+            .map(|((face, embedding), track_idx)| match track_idx {
+                Some(idx) => {
+                    let track = &mut self.tracks[idx];
+                    track.face = track
+                        .filter
+                        .apply(face, frame_interval * (track.misses + 1) as f32);
+                    track.hits += 1;
+                    track.misses = 0;
+                    track.update_embedding(embedding);
+                    track.id
+                }
+                None => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    let mut filter = FaceFilter::new(&self.config.smoothing);
+                    self.tracks.push(Track {
+                        id,
+                        face: filter.apply(face, frame_interval),
+                        hits: 1,
+                        misses: 0,
+                        embedding,
+                        filter,
+                    });
+                    id
+                }
+            })
+            .collect();
+
+        let max_misses = self.config.max_misses;
+        self.tracks.retain(|t| t.misses <= max_misses);
+        ids
+    }
+
+    fn match_score(
+        &self,
+        track: &Track,
+        face: &Face,
+        embedding: Option<&Embedding>,
+    ) -> Option<f32> {
+        let iou = track.face.iou(face);
+        let similarity = match (track.embedding.as_ref(), embedding) {
+            (Some(a), Some(b)) if self.config.use_embedding => Some(a.dot(b)),
+            _ => None,
+        };
+
+        match similarity {
+            Some(sim) => {
+                if iou < self.config.iou_threshold && sim < self.config.embedding_threshold {
+                    return None;
+                }
+                let w = self.config.embedding_weight.clamp(0., 1.);
+                Some(iou * (1. - w) + sim * w)
+            }
+            None => (iou >= self.config.iou_threshold).then_some(iou),
+        }
+    }
+}
+
+fn normalize(embedding: Embedding) -> Embedding {
+    let norm = embedding.dot(&embedding).sqrt();
+    if norm == 0. || !norm.is_finite() {
+        return embedding;
+    }
+    embedding / norm
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Tracker, TrackerConfig};
+    use crate::model::data::{Face, KeyPoints, VectorizedTensor};
+
+    fn face_at(x: f32, y: f32) -> Face {
+        Face {
+            score: 0.9,
+            bbox: (x, y, x + 50., y + 50.),
+            keypoints: KeyPoints([[x + 15., y + 20.]; 5]),
+        }
+    }
+
+    fn embedding(hot: usize) -> VectorizedTensor {
+        VectorizedTensor::new(ndarray::Array2::from_shape_fn((1, 8), |(_, i)| {
+            if i == hot {
+                1.
+            } else {
+                0.
+            }
+        }))
+    }
+
+    #[test]
+    fn keeps_id_of_moving_faces() {
+        let mut tracker = Tracker::new(TrackerConfig::default());
+        let first = tracker.update(&[face_at(0., 0.), face_at(200., 0.)]);
+        assert_eq!(first, [0, 1]);
+
+        // detection order flipped & slightly moved
+        let second = tracker.update(&[face_at(205., 3.), face_at(4., 2.)]);
+        assert_eq!(
+            second,
+            [1, 0],
+            "Ids should follow faces, not detection order"
+        );
+
+        let third = tracker.update(&[face_at(8., 4.), face_at(400., 0.)]);
+        assert_eq!(third, [0, 2], "New face should get a fresh id");
+    }
+
+    #[test]
+    fn drops_track_after_max_misses() {
+        let mut tracker = Tracker::new(TrackerConfig {
+            max_misses: 2,
+            ..Default::default()
+        });
+        tracker.update(&[face_at(0., 0.)]);
+        tracker.update(&[]);
+        tracker.update(&[]);
+        assert_eq!(
+            tracker.update(&[face_at(0., 0.)]),
+            [0],
+            "Track within max misses"
+        );
+
+        for _ in 0..3 {
+            tracker.update(&[]);
+        }
+        assert!(tracker.tracks().is_empty());
+        assert_eq!(tracker.update(&[face_at(0., 0.)]), [1]);
+    }
+
+    #[test]
+    fn matches_crossing_faces_by_embedding() {
+        let mut tracker = Tracker::new(TrackerConfig {
+            use_embedding: true,
+            embedding_weight: 0.8,
+            ..Default::default()
+        });
+        let (a, b) = (embedding(0), embedding(1));
+        tracker.update_with_embeddings(&[face_at(0., 0.), face_at(30., 0.)], &[Some(&a), Some(&b)]);
+
+        // faces crossed, box overlap alone would swap ids
+        let ids = tracker
+            .update_with_embeddings(&[face_at(2., 0.), face_at(28., 0.)], &[Some(&b), Some(&a)]);
+        assert_eq!(ids, [1, 0]);
+    }
+}
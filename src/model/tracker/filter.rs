@@ -0,0 +1,305 @@
+// Temporal smoothing of tracked detections
+// One Euro | https://gery.casiez.net/1euro/
+// Kalman | constant velocity model per coordinate
+
+use crate::model::data::Face;
+
+// bbox (4) + keypoints (5 * 2)
+const FACE_VALUES_LEN: usize = 14;
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Smoothing {
+    None,
+    OneEuro {
+        /// Hz, lower is smoother when still
+        min_cutoff: f32,
+        /// Cutoff increase per pixel/s of speed, higher lags less when moving
+        beta: f32,
+        /// Hz, cutoff of the speed estimate
+        d_cutoff: f32,
+    },
+    Kalman {
+        /// Acceleration variance, higher follows motion faster
+        process_noise: f32,
+        /// Detection jitter variance in pixels^2
+        measurement_noise: f32,
+    },
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Smoothing::OneEuro {
+            min_cutoff: 1.,
+            beta: 0.05,
+            d_cutoff: 1.,
+        }
+    }
+}
+
+impl Smoothing {
+    fn scalar_filter(&self) -> ScalarFilter {
+        match self {
+            Smoothing::None => ScalarFilter::Passthrough,
+            Smoothing::OneEuro {
+                min_cutoff,
+                beta,
+                d_cutoff,
+            } => ScalarFilter::OneEuro(OneEuroFilter::new(*min_cutoff, *beta, *d_cutoff)),
+            Smoothing::Kalman {
+                process_noise,
+                measurement_noise,
+            } => ScalarFilter::Kalman(KalmanFilter::new(*process_noise, *measurement_noise)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ScalarFilter {
+    Passthrough,
+    OneEuro(OneEuroFilter),
+    Kalman(KalmanFilter),
+}
+
+impl ScalarFilter {
+    /// `dt` seconds since the previous value
+    pub fn filter(&mut self, value: f32, dt: f32) -> f32 {
+        if !value.is_finite() {
+            return value;
+        }
+        let dt = if dt.is_normal() && dt > 0. {
+            dt
+        } else {
+            1. / 30.
+        };
+        match self {
+            ScalarFilter::Passthrough => value,
+            ScalarFilter::OneEuro(f) => f.filter(value, dt),
+            ScalarFilter::Kalman(f) => f.filter(value, dt),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OneEuroFilter {
+    min_cutoff: f32,
+    beta: f32,
+    d_cutoff: f32,
+    // (value, derivative)
+    prev: Option<(f32, f32)>,
+}
+
+impl OneEuroFilter {
+    pub fn new(min_cutoff: f32, beta: f32, d_cutoff: f32) -> Self {
+        Self {
+            min_cutoff,
+            beta,
+            d_cutoff,
+            prev: None,
+        }
+    }
+
+    fn filter(&mut self, value: f32, dt: f32) -> f32 {
+        let Some((prev_value, prev_d)) = self.prev else {
+            self.prev = Some((value, 0.));
+            return value;
+        };
+
+        let d = lerp(
+            smoothing_factor(self.d_cutoff, dt),
+            (value - prev_value) / dt,
+            prev_d,
+        );
+        let cutoff = self.min_cutoff + self.beta * d.abs();
+        let value = lerp(smoothing_factor(cutoff, dt), value, prev_value);
+
+        self.prev = Some((value, d));
+        value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KalmanFilter {
+    process_noise: f32,
+    measurement_noise: f32,
+    // (position, velocity)
+    state: Option<(f32, f32)>,
+    covariance: [[f32; 2]; 2],
+}
+
+impl KalmanFilter {
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        Self {
+            process_noise,
+            measurement_noise,
+            state: None,
+            covariance: [[0.; 2]; 2],
+        }
+    }
+
+    fn filter(&mut self, value: f32, dt: f32) -> f32 {
+        let Some((x, v)) = self.state else {
+            self.state = Some((value, 0.));
+            self.covariance = [[self.measurement_noise, 0.], [0., self.measurement_noise]];
+            return value;
+        };
+
+        // predict, x' = F x | P' = F P F^t + Q
+        let (x, v) = (x + v * dt, v);
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let q = self.process_noise;
+        let (p00, p01, p10, p11) = (
+            p00 + dt * (p10 + p01) + dt * dt * p11 + q * dt.powi(3) / 3.,
+            p01 + dt * p11 + q * dt * dt / 2.,
+            p10 + dt * p11 + q * dt * dt / 2.,
+            p11 + q * dt,
+        );
+
+        // update with position measurement
+        let s = p00 + self.measurement_noise;
+        let (k0, k1) = (p00 / s, p10 / s);
+        let residual = value - x;
+        let (x, v) = (x + k0 * residual, v + k1 * residual);
+        self.covariance = [
+            [(1. - k0) * p00, (1. - k0) * p01],
+            [p10 - k1 * p00, p11 - k1 * p01],
+        ];
+
+        self.state = Some((x, v));
+        x
+    }
+}
+
+/// Per coordinate filters of a single tracked face
+#[derive(Debug, Clone)]
+pub struct FaceFilter(Vec<ScalarFilter>);
+
+impl FaceFilter {
+    pub fn new(smoothing: &Smoothing) -> Self {
+        Self(vec![smoothing.scalar_filter(); FACE_VALUES_LEN])
+    }
+
+    /// Smoothed copy of `face`, score is kept as detected
+    pub fn apply(&mut self, face: &Face, dt: f32) -> Face {
+        let mut values = [face.bbox.0, face.bbox.1, face.bbox.2, face.bbox.3]
+            .into_iter()
+            .chain(face.keypoints.iter().flatten().copied())
+            .zip(self.0.iter_mut())
+            .map(|(v, f)| f.filter(v, dt));
+
+        let mut next = || values.next().unwrap_or_default();
+        let bbox = (next(), next(), next(), next());
+        let mut face = Face {
+            bbox,
+            ..face.clone()
+        };
+        face.keypoints
+            .iter_mut()
+            .for_each(|kp| *kp = [next(), next()]);
+        face
+    }
+}
+
+// cutoff (Hz) to exponential smoothing factor
+fn smoothing_factor(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1. / (2. * std::f32::consts::PI * cutoff);
+    1. / (1. + tau / dt)
+}
+
+fn lerp(alpha: f32, value: f32, prev: f32) -> f32 {
+    alpha * value + (1. - alpha) * prev
+}
+
+#[cfg(test)]
+mod test {
+    use rand::Rng;
+
+    use super::{FaceFilter, Smoothing};
+    use crate::model::data::{Face, KeyPoints};
+
+    const DT: f32 = 1. / 30.;
+
+    fn jitter_deviation(smoothing: &Smoothing) -> f32 {
+        let mut rand = rand::thread_rng();
+        let mut filter = FaceFilter::new(smoothing);
+        (0..120)
+            .map(|_| {
+                let j = rand.gen_range(-3.0..3.0);
+                let face = Face {
+                    score: 0.9,
+                    bbox: (100. + j, 80. - j, 200. + j, 220. - j),
+                    keypoints: KeyPoints([[130. + j, 130. - j]; 5]),
+                };
+                filter.apply(&face, DT)
+            })
+            // skip warm up
+            .skip(30)
+            .map(|face| ((face.bbox.0 - 100.).abs() + (face.keypoints[0][1] - 130.).abs()) / 2.)
+            .sum::<f32>()
+            / 90.
+    }
+
+    #[test]
+    fn passthrough_keeps_face() {
+        let face = Face {
+            score: 0.5,
+            bbox: (1., 2., 3., 4.),
+            keypoints: KeyPoints([[5., 6.], [7., 8.], [9., 10.], [11., 12.], [13., 14.]]),
+        };
+        let filtered = FaceFilter::new(&Smoothing::None).apply(&face, DT);
+        assert_eq!(filtered.bbox, face.bbox);
+        assert_eq!(filtered.keypoints.0, face.keypoints.0);
+    }
+
+    #[test]
+    fn filters_reduce_jitter() {
+        for smoothing in [
+            Smoothing::default(),
+            Smoothing::Kalman {
+                process_noise: 100.,
+                measurement_noise: 4.,
+            },
+        ] {
+            let deviation = jitter_deviation(&smoothing);
+            assert!(
+                deviation < 1.,
+                "{:?} should damp +-3px (1.5px mean) jitter, got {}",
+                smoothing,
+                deviation
+            );
+        }
+    }
+
+    #[test]
+    fn filters_follow_motion() {
+        for smoothing in [
+            Smoothing::default(),
+            Smoothing::Kalman {
+                process_noise: 100.,
+                measurement_noise: 4.,
+            },
+        ] {
+            let mut filter = FaceFilter::new(&smoothing);
+            let last = (0..90)
+                .map(|i| {
+                    // 150px/s pan
+                    let x = i as f32 * 5.;
+                    filter.apply(
+                        &Face {
+                            score: 0.9,
+                            bbox: (x, 0., x + 100., 100.),
+                            keypoints: KeyPoints([[x + 50., 50.]; 5]),
+                        },
+                        DT,
+                    )
+                })
+                .last()
+                .expect("Missing filtered face");
+            assert!(
+                (last.bbox.0 - 445.).abs() < 10.,
+                "{:?} lags behind, got {}",
+                smoothing,
+                last.bbox.0
+            );
+        }
+    }
+}
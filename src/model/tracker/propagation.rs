@@ -0,0 +1,508 @@
+// Cheap frame to frame motion of tracked faces, used between detection passes
+
+use opencv::core;
+
+use crate::{
+    model::data::{ChannelOrder, Face, Tensor},
+    Error, Result,
+};
+
+use super::Track;
+
+// 0 ~ 255 luma, (h, w)
+type Gray = ndarray::Array2<f32>;
+
+// samples per axis of the local search template
+const SEARCH_GRID: usize = 16;
+// frames between detection stats reports
+const REPORT_INTERVAL: usize = 300;
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Propagation {
+    /// Block matching of the face patch around its last position
+    LocalSearch {
+        /// Pixels searched in each direction
+        radius: usize,
+        /// Mean absolute luma difference considered as tracking loss
+        max_error: f32,
+    },
+    /// Pyramidal Lucas-Kanade flow of keypoints (cv2.calcOpticalFlowPyrLK)
+    OpticalFlow {
+        /// Share of points that must be found, otherwise tracking is lost
+        min_found: f32,
+    },
+}
+
+impl Default for Propagation {
+    fn default() -> Self {
+        Propagation::LocalSearch {
+            radius: 24,
+            max_error: 24.,
+        }
+    }
+}
+
+/// Detection on crops around tracked faces, keeps small faces of large frames detectable
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RoiDetection {
+    /// Crop side relative to the larger side of the face bbox
+    pub scale: f32,
+    /// Full frame detection every n detection passes, picks up new faces
+    pub full_frame_interval: usize,
+}
+
+impl Default for RoiDetection {
+    fn default() -> Self {
+        Self {
+            scale: 2.5,
+            full_frame_interval: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct DetectionSchedule {
+    /// Detection every n frames, 1 detects on every frame
+    pub interval: usize,
+    pub propagation: Propagation,
+    /// Detect on crops around tracked faces instead of the whole frame
+    pub roi: Option<RoiDetection>,
+}
+
+impl Default for DetectionSchedule {
+    fn default() -> Self {
+        Self {
+            interval: 1,
+            propagation: Propagation::default(),
+            roi: None,
+        }
+    }
+}
+
+/// How faces of a frame were found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    FullDetection,
+    RoiDetection,
+    Propagated,
+}
+
+/// Moves tracked faces from the previous frame onto the next one
+#[derive(Debug, Default)]
+pub struct Propagator {
+    schedule: DetectionSchedule,
+    prev: Option<Gray>,
+    since_detection: usize,
+    // detection passes since the last full frame one
+    since_full_frame: usize,
+    stats: DetectionStats,
+}
+
+impl Propagator {
+    pub fn new(schedule: DetectionSchedule) -> Self {
+        Self {
+            schedule,
+            ..Default::default()
+        }
+    }
+
+    pub fn stats(&self) -> &DetectionStats {
+        &self.stats
+    }
+
+    pub fn reset(&mut self) {
+        self.prev = None;
+        self.since_detection = 0;
+        self.since_full_frame = 0;
+        self.stats = DetectionStats::default();
+    }
+
+    /// Tracked faces moved onto `frame`, `None` when detection is due or tracking is lost
+    pub fn propagate(&mut self, frame: &Tensor, tracks: &[Track]) -> Result<Option<Vec<Face>>> {
+        // detecting every frame, no previous luma is ever compared
+        if self.schedule.interval <= 1 {
+            return Ok(None);
+        }
+        let next = luma(frame);
+        let prev = self.prev.replace(next);
+        let (Some(prev), Some(next)) = (prev, self.prev.as_ref()) else {
+            return Ok(None);
+        };
+
+        let visible = tracks
+            .iter()
+            .filter(|t| t.misses == 0)
+            .collect::<Vec<&Track>>();
+        if self.since_detection + 1 >= self.schedule.interval || visible.is_empty() {
+            return Ok(None);
+        }
+
+        let mut faces = Vec::with_capacity(visible.len());
+        for track in visible {
+            let face = match self.schedule.propagation {
+                Propagation::LocalSearch { radius, max_error } => {
+                    local_search(&prev, next, &track.face, radius, max_error)
+                }
+                Propagation::OpticalFlow { min_found } => {
+                    optical_flow(&prev, next, &track.face, min_found)?
+                }
+            };
+            let Some(face) = face else {
+                tracing::debug!("Lost track {}, detecting", track.id);
+                return Ok(None);
+            };
+            faces.push(face);
+        }
+        Ok(Some(faces))
+    }
+
+    /// Square (x1, y1, x2, y2) crops around visible tracks, `None` when a full frame pass is due
+    pub fn roi_regions(
+        &self,
+        tracks: &[Track],
+        frame_size: (usize, usize),
+    ) -> Option<Vec<(usize, usize, usize, usize)>> {
+        let roi = self.schedule.roi.as_ref()?;
+        if self.since_full_frame + 1 >= roi.full_frame_interval.max(1) {
+            return None;
+        }
+
+        let regions = tracks
+            .iter()
+            .filter(|t| t.misses == 0)
+            .map(|t| roi_region(&t.face, roi.scale, frame_size))
+            .collect::<Vec<_>>();
+        (!regions.is_empty()).then_some(regions)
+    }
+
+    /// Records how the latest frame got its faces & how long the frame took
+    pub fn record(&mut self, kind: FrameKind, elapsed: std::time::Duration) {
+        match kind {
+            FrameKind::FullDetection => {
+                self.since_detection = 0;
+                self.since_full_frame = 0;
+            }
+            FrameKind::RoiDetection => {
+                self.since_detection = 0;
+                self.since_full_frame += 1;
+            }
+            FrameKind::Propagated => self.since_detection += 1,
+        }
+        self.stats.record(kind, elapsed);
+
+        let scheduled = self.schedule.interval > 1 || self.schedule.roi.is_some();
+        if self.stats.frames.is_multiple_of(REPORT_INTERVAL) && scheduled {
+            tracing::info!(
+                "Full frame detection on {}/{} frames ({} roi), {:.1} fps (x{:.2} over full frame detection every frame)",
+                self.stats.detections,
+                self.stats.frames,
+                self.stats.roi_detections,
+                self.stats.fps(),
+                self.stats.fps_gain()
+            );
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DetectionStats {
+    pub frames: usize,
+    /// Full frame detections
+    pub detections: usize,
+    pub roi_detections: usize,
+    pub detect_time: std::time::Duration,
+    pub roi_time: std::time::Duration,
+    pub propagate_time: std::time::Duration,
+}
+
+impl DetectionStats {
+    fn record(&mut self, kind: FrameKind, elapsed: std::time::Duration) {
+        self.frames += 1;
+        match kind {
+            FrameKind::FullDetection => {
+                self.detections += 1;
+                self.detect_time += elapsed;
+            }
+            FrameKind::RoiDetection => {
+                self.roi_detections += 1;
+                self.roi_time += elapsed;
+            }
+            FrameKind::Propagated => self.propagate_time += elapsed,
+        }
+    }
+
+    fn total_secs(&self) -> f32 {
+        (self.detect_time + self.roi_time + self.propagate_time).as_secs_f32()
+    }
+
+    pub fn fps(&self) -> f32 {
+        let total = self.total_secs();
+        if total == 0. {
+            return 0.;
+        }
+        self.frames as f32 / total
+    }
+
+    /// Estimated speed up over running detection on every frame
+    pub fn fps_gain(&self) -> f32 {
+        let total = self.total_secs();
+        if self.detections == 0 || total == 0. {
+            return 1.;
+        }
+        let detect_frame = self.detect_time.as_secs_f32() / self.detections as f32;
+        detect_frame * self.frames as f32 / total
+    }
+}
+
+fn luma(tensor: &Tensor) -> Gray {
+    let (_, channels, h, w) = tensor.dim();
+    let pixel = |c: usize, y: usize, x: usize| tensor.normal.to_pixel(c, tensor[(0, c, y, x)]);
+    Gray::from_shape_fn((h, w), |(y, x)| {
+        if channels < 3 {
+            return pixel(0, y, x);
+        }
+        let (r, b) = match tensor.order {
+            ChannelOrder::Rgb => (0, 2),
+            ChannelOrder::Bgr => (2, 0),
+        };
+        0.299 * pixel(r, y, x) + 0.587 * pixel(1, y, x) + 0.114 * pixel(b, y, x)
+    })
+}
+
+fn roi_region(face: &Face, scale: f32, (w, h): (usize, usize)) -> (usize, usize, usize, usize) {
+    let (cx, cy) = (
+        (face.bbox.0 + face.bbox.2) / 2.,
+        (face.bbox.1 + face.bbox.3) / 2.,
+    );
+    let side = ((face.bbox.2 - face.bbox.0).max(face.bbox.3 - face.bbox.1) * scale.max(1.))
+        .min(w.min(h) as f32)
+        .max(1.);
+
+    // shifted back inside the frame rather than clipped, keeps the crop square
+    let x1 = (cx - side / 2.).clamp(0., w as f32 - side);
+    let y1 = (cy - side / 2.).clamp(0., h as f32 - side);
+    let side = side as usize;
+    (
+        x1 as usize,
+        y1 as usize,
+        x1 as usize + side,
+        y1 as usize + side,
+    )
+}
+
+fn local_search(
+    prev: &Gray,
+    next: &Gray,
+    face: &Face,
+    radius: usize,
+    max_error: f32,
+) -> Option<Face> {
+    let (h, w) = prev.dim();
+    let (x1, y1) = (face.bbox.0.max(0.), face.bbox.1.max(0.));
+    let (x2, y2) = (
+        face.bbox.2.min(w as f32 - 1.),
+        face.bbox.3.min(h as f32 - 1.),
+    );
+    if x2 - x1 < 2. || y2 - y1 < 2. {
+        return None;
+    }
+
+    let samples = (0..SEARCH_GRID)
+        .flat_map(|gy| {
+            (0..SEARCH_GRID).map(move |gx| {
+                let step = (SEARCH_GRID - 1) as f32;
+                (
+                    (x1 + (x2 - x1) * gx as f32 / step) as isize,
+                    (y1 + (y2 - y1) * gy as f32 / step) as isize,
+                )
+            })
+        })
+        .map(|(x, y)| (x, y, prev[(y as usize, x as usize)]))
+        .collect::<Vec<(isize, isize, f32)>>();
+
+    let error = |dx: isize, dy: isize| {
+        samples
+            .iter()
+            .map(|(x, y, v)| {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= w as isize || ny >= h as isize {
+                    return 255.;
+                }
+                (next[(ny as usize, nx as usize)] - v).abs()
+            })
+            .sum::<f32>()
+            / samples.len() as f32
+    };
+
+    let radius = radius as isize;
+    let mut best = (error(0, 0), 0, 0);
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let err = error(dx, dy);
+            if err < best.0 {
+                best = (err, dx, dy);
+            }
+        }
+    }
+
+    let (err, dx, dy) = best;
+    (err <= max_error).then(|| face.offset(dx as f32, dy as f32))
+}
+
+fn optical_flow(prev: &Gray, next: &Gray, face: &Face, min_found: f32) -> Result<Option<Face>> {
+    let prev_pts = face
+        .keypoints
+        .iter()
+        .map(|[x, y]| core::Point2f::new(*x, *y))
+        .chain([core::Point2f::new(
+            (face.bbox.0 + face.bbox.2) / 2.,
+            (face.bbox.1 + face.bbox.3) / 2.,
+        )])
+        .collect::<core::Vector<core::Point2f>>();
+    let mut next_pts = core::Vector::<core::Point2f>::new();
+    let mut status = core::Vector::<u8>::new();
+    let mut err = core::Vector::<f32>::new();
+
+    opencv::video::calc_optical_flow_pyr_lk(
+        &gray_mat(prev)?,
+        &gray_mat(next)?,
+        &prev_pts,
+        &mut next_pts,
+        &mut status,
+        &mut err,
+        core::Size::new(21, 21),
+        3,
+        core::TermCriteria::new(core::TermCriteria_COUNT + core::TermCriteria_EPS, 30, 0.01)
+            .map_err(Error::CVError)?,
+        0,
+        1e-4,
+    )
+    .map_err(Error::CVError)?;
+
+    // (dx, dy) of found points
+    let mut moves = prev_pts
+        .iter()
+        .zip(next_pts.iter())
+        .zip(status.to_vec())
+        .map(|((p, n), found)| (found != 0).then_some((n.x - p.x, n.y - p.y)))
+        .collect::<Vec<Option<(f32, f32)>>>();
+    let found = moves.iter().flatten().count();
+    if found == 0 || (found as f32) < prev_pts.len() as f32 * min_found {
+        return Ok(None);
+    }
+
+    let (mut xs, mut ys): (Vec<f32>, Vec<f32>) = moves.iter().flatten().copied().unzip();
+    xs.sort_by(f32::total_cmp);
+    ys.sort_by(f32::total_cmp);
+    let median = (xs[xs.len() / 2], ys[ys.len() / 2]);
+
+    let mut moved = face.offset(median.0, median.1);
+    // keypoints follow their own flow when found
+    moves.truncate(moved.keypoints.len());
+    for ((kp, prev_kp), m) in moved
+        .keypoints
+        .iter_mut()
+        .zip(face.keypoints.iter())
+        .zip(moves)
+    {
+        if let Some((dx, dy)) = m {
+            *kp = [prev_kp[0] + dx, prev_kp[1] + dy];
+        }
+    }
+    Ok(Some(moved))
+}
+
+fn gray_mat(gray: &Gray) -> Result<core::Mat> {
+    let (h, w) = gray.dim();
+    let bytes = gray
+        .iter()
+        .map(|v| v.clamp(0., 255.) as u8)
+        .collect::<Vec<u8>>();
+    Ok(
+        core::Mat::new_rows_cols_with_data::<u8>(h as i32, w as i32, &bytes)
+            .map_err(Error::CVError)?
+            .clone_pointee(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{local_search, roi_region, DetectionSchedule, Gray, Propagator};
+    use crate::model::{
+        data::{Face, KeyPoints},
+        Tensor, TensorData,
+    };
+
+    // smooth blob centered at (cx, cy)
+    fn blob_frame(cx: f32, cy: f32) -> Gray {
+        Gray::from_shape_fn((120, 160), |(y, x)| {
+            let d2 = (x as f32 - cx).powi(2) + (y as f32 - cy).powi(2);
+            255. * (-d2 / 300.).exp()
+        })
+    }
+
+    #[test]
+    fn local_search_follows_moved_face() {
+        let face = Face {
+            score: 0.9,
+            bbox: (50., 30., 90., 70.),
+            keypoints: KeyPoints([[70., 50.]; 5]),
+        };
+        let (prev, next) = (blob_frame(70., 50.), blob_frame(77., 45.));
+
+        let moved = local_search(&prev, &next, &face, 12, 10.).expect("Lost moved face");
+        assert_eq!(moved.bbox, (57., 25., 97., 65.));
+        assert_eq!(moved.keypoints[0], [77., 45.]);
+    }
+
+    #[test]
+    fn skips_luma_when_detecting_every_frame() {
+        let frame = Tensor::from(TensorData::zeros((1, 3, 8, 8)));
+        let mut propagator = Propagator::new(DetectionSchedule::default());
+        for _ in 0..2 {
+            assert!(propagator
+                .propagate(&frame, &[])
+                .expect("Failed to propagate")
+                .is_none());
+        }
+        assert!(propagator.prev.is_none(), "Luma computed for interval 1");
+
+        let mut propagator = Propagator::new(DetectionSchedule {
+            interval: 3,
+            ..Default::default()
+        });
+        let _ = propagator.propagate(&frame, &[]);
+        assert!(propagator.prev.is_some());
+    }
+
+    #[test]
+    fn roi_region_stays_square_inside_frame() {
+        let face = Face {
+            score: 0.9,
+            bbox: (10., 400., 50., 450.),
+            keypoints: KeyPoints([[30., 420.]; 5]),
+        };
+        // 50px face near the bottom left corner of 1080p
+        let (x1, y1, x2, y2) = roi_region(&face, 2., (1920, 1080));
+        assert_eq!((x2 - x1, y2 - y1), (100, 100));
+        assert_eq!((x1, y1), (0, 375));
+
+        let corner = face.offset(1900., 650.);
+        let (x1, y1, x2, y2) = roi_region(&corner, 2., (1920, 1080));
+        assert_eq!((x1, y1, x2, y2), (1820, 980, 1920, 1080));
+    }
+
+    #[test]
+    fn local_search_reports_loss() {
+        let face = Face {
+            score: 0.9,
+            bbox: (50., 30., 90., 70.),
+            keypoints: KeyPoints([[70., 50.]; 5]),
+        };
+        let next = Gray::zeros((120, 160));
+
+        assert!(local_search(&blob_frame(70., 50.), &next, &face, 12, 10.).is_none());
+    }
+}
@@ -1,78 +1,203 @@
-use crate::{Error, Result};
-
-use super::{
-    data::{get_tensor_ref, VectorizedTensor},
-    ArcCudaDevice, InputSizeMatrix, Tensor,
-};
-
-pub struct VectorizationModel {
-    input_size: (usize, usize),
-    input_size_mat: InputSizeMatrix,
-    session: ort::Session,
-}
-
-impl VectorizationModel {
-    // w600k_r50.onnx
-    #[tracing::instrument(name = "Initialize recognition model", err)]
-    pub fn new(onnx_path: std::path::PathBuf) -> Result<Self> {
-        Ok(Self {
-            input_size: (112, 112),
-            input_size_mat: InputSizeMatrix::from_shape_fn((1, 3, 112, 112), |d| d),
-            session: super::start_session_from_file(onnx_path)?,
-        })
-    }
-
-    // (n, 3, 112, 112)
-    pub fn run(
-        &mut self,
-        mut tensor: Tensor,
-        cuda_device: Option<&ArcCudaDevice>,
-    ) -> Result<VectorizedTensor> {
-        // (n, c, h, w)
-        let (_, _, dy, dx) = tensor.dim();
-        if dy != self.input_size.1 && dx != self.input_size.0 {
-            tensor = tensor.resize_with_matrix(&mut self.input_size_mat);
-        }
-        if let Some(cuda) = cuda_device {
-            self.run_with_cuda(tensor, cuda)
-        } else {
-            self.run_with_cpu(tensor)
-        }
-    }
-
-    pub fn run_with_cpu(&self, tensor: Tensor) -> Result<VectorizedTensor> {
-        let outputs = self
-            .session
-            .run(ort::inputs![tensor.data].map_err(Error::ModelError)?)
-            .map_err(Error::ModelError)?;
-
-        Ok(outputs[0]
-            .try_extract_tensor::<f32>()
-            .map_err(Error::ModelError)?
-            .to_shape((1, 512))
-            .map_err(Error::as_unknown_error)?
-            .to_owned()
-            .into())
-    }
-
-    pub fn run_with_cuda(&self, tensor: Tensor, cuda: &ArcCudaDevice) -> Result<VectorizedTensor> {
-        let dim = tensor.dim();
-        let device_data = tensor.to_cuda_slice(cuda)?;
-        let tensor = get_tensor_ref(
-            &device_data,
-            vec![dim.0 as i64, dim.1 as i64, dim.2 as i64, dim.3 as i64],
-        )?;
-        let outputs = self
-            .session
-            .run([tensor.into()])
-            .map_err(Error::ModelError)?;
-
-        Ok(outputs[0]
-            .try_extract_tensor::<f32>()
-            .map_err(Error::ModelError)?
-            .to_shape((1, 512))
-            .map_err(Error::as_unknown_error)?
-            .into_owned()
-            .into())
-    }
-}
+use crate::{Error, Result};
+
+use super::{
+    data::{get_tensor_ref, InputFormat, Interpolation, VectorizedTensor},
+    ArcCudaDevice, InputSizeMatrix, Tensor,
+};
+
+// ArcFace r50 embedding length, used when the model output is dynamic
+const DEFAULT_EMBEDDING_SIZE: usize = 512;
+
+/// Recognition model, input normalization is `ModelConfig::recognition_input`
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct RecognitionConfig {
+    /// Onnx file in the models directory
+    pub file: String,
+    /// (w, h) of the aligned face crop
+    pub input_size: (usize, usize),
+    /// Output embedding length, read from the model when unset
+    pub embedding_size: Option<usize>,
+    /// L2 normalize embeddings, for models with unnormalized features (AdaFace)
+    pub l2_normalize: bool,
+}
+
+impl Default for RecognitionConfig {
+    fn default() -> Self {
+        Self {
+            file: "w600k_r50.onnx".into(),
+            input_size: (112, 112),
+            embedding_size: None,
+            l2_normalize: false,
+        }
+    }
+}
+
+pub struct VectorizationModel {
+    input_size: (usize, usize),
+    input_format: InputFormat,
+    input_size_mat: InputSizeMatrix,
+    embedding_size: usize,
+    l2_normalize: bool,
+    session: ort::Session,
+}
+
+impl VectorizationModel {
+    // w600k_r50.onnx
+    #[tracing::instrument(name = "Initialize recognition model", skip(config), err)]
+    pub fn new(
+        onnx_path: std::path::PathBuf,
+        input_format: InputFormat,
+        config: &RecognitionConfig,
+    ) -> Result<Self> {
+        let session = super::start_session_from_file(onnx_path)?;
+        let model_size = session
+            .outputs
+            .first()
+            .and_then(|o| o.output_type.tensor_dimensions())
+            .and_then(|dims| dims.last().copied())
+            .filter(|d| *d > 0)
+            .map(|d| d as usize);
+
+        let embedding_size = match (config.embedding_size, model_size) {
+            (Some(size), Some(model)) if size != model => {
+                return Err(Error::InvalidModelIOError(format!(
+                    "Recognition embedding size {} doesn't match model output {}",
+                    size, model
+                )))
+            }
+            (Some(size), _) | (None, Some(size)) => size,
+            (None, None) => DEFAULT_EMBEDDING_SIZE,
+        };
+        let (w, h) = config.input_size;
+
+        Ok(Self {
+            input_size: config.input_size,
+            input_format,
+            input_size_mat: InputSizeMatrix::from_shape_fn((1, 3, h, w), |d| d),
+            embedding_size,
+            l2_normalize: config.l2_normalize,
+            session,
+        })
+    }
+
+    pub fn embedding_size(&self) -> usize {
+        self.embedding_size
+    }
+
+    // (n, 3, h, w) -> (n, embedding_size)
+    pub fn run(
+        &mut self,
+        mut tensor: Tensor,
+        cuda_device: Option<&ArcCudaDevice>,
+    ) -> Result<VectorizedTensor> {
+        // (n, c, h, w)
+        let (_, _, dy, dx) = tensor.dim();
+        if dy != self.input_size.1 || dx != self.input_size.0 {
+            tensor = tensor.resize_with_matrix(
+                &mut self.input_size_mat,
+                Interpolation::for_resize((dx, dy), self.input_size, Interpolation::Bilinear),
+            );
+        }
+        tensor.to_format(&self.input_format);
+        if let Some(cuda) = cuda_device {
+            self.run_with_cuda(tensor, cuda)
+        } else {
+            self.run_with_cpu(tensor)
+        }
+    }
+
+    pub fn run_with_cpu(&self, tensor: Tensor) -> Result<VectorizedTensor> {
+        let outputs = self
+            .session
+            .run(ort::inputs![tensor.data].map_err(Error::ModelError)?)
+            .map_err(Error::ModelError)?;
+
+        self.embeddings(
+            &outputs[0]
+                .try_extract_tensor::<f32>()
+                .map_err(Error::ModelError)?,
+        )
+    }
+
+    pub fn run_with_cuda(&self, tensor: Tensor, cuda: &ArcCudaDevice) -> Result<VectorizedTensor> {
+        let dim = tensor.dim();
+        let device_data = tensor.to_cuda_slice(cuda)?;
+        let tensor = get_tensor_ref(
+            &device_data,
+            vec![dim.0 as i64, dim.1 as i64, dim.2 as i64, dim.3 as i64],
+        )?;
+        let outputs = self
+            .session
+            .run([tensor.into()])
+            .map_err(Error::ModelError)?;
+
+        self.embeddings(
+            &outputs[0]
+                .try_extract_tensor::<f32>()
+                .map_err(Error::ModelError)?,
+        )
+    }
+
+    fn embeddings(&self, output: &ndarray::ArrayViewD<'_, f32>) -> Result<VectorizedTensor> {
+        to_embeddings(output, self.embedding_size, self.l2_normalize)
+    }
+}
+
+// (n, embedding_size), a row per input face
+fn to_embeddings(
+    output: &ndarray::ArrayViewD<'_, f32>,
+    embedding_size: usize,
+    l2_normalize: bool,
+) -> Result<VectorizedTensor> {
+    let len = output.len();
+    if len == 0 || embedding_size == 0 || !len.is_multiple_of(embedding_size) {
+        return Err(Error::InvalidModelIOError(format!(
+            "Recognition output of {} values doesn't fit embedding size {}",
+            len, embedding_size
+        )));
+    }
+
+    let mut embeddings = output
+        .to_shape((len / embedding_size, embedding_size))
+        .map_err(Error::as_unknown_error)?
+        .into_owned();
+    if l2_normalize {
+        embeddings.rows_mut().into_iter().for_each(|mut row| {
+            let norm = row.dot(&row).sqrt();
+            if norm > 0. && norm.is_finite() {
+                row /= norm;
+            }
+        });
+    }
+    Ok(embeddings.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_embeddings;
+
+    #[test]
+    fn reshapes_batched_embeddings() {
+        // AdaFace style (2, 512) unnormalized features
+        let output =
+            ndarray::Array2::from_shape_fn((2, 512), |(n, i)| (n + i % 3) as f32 + 1.).into_dyn();
+
+        let raw = to_embeddings(&output.view(), 512, false).expect("Failed to reshape");
+        assert_eq!(raw.dim(), (2, 512));
+        assert_eq!(raw[(1, 2)], 4.);
+
+        let normed = to_embeddings(&output.view(), 512, true).expect("Failed to normalize");
+        for row in normed.rows() {
+            assert!(
+                (row.dot(&row) - 1.).abs() < 1e-4,
+                "Rows should be unit length"
+            );
+        }
+
+        assert!(
+            to_embeddings(&output.view(), 300, false).is_err(),
+            "Mismatched embedding size"
+        );
+    }
+}
@@ -1,95 +1,212 @@
-use std::{
-    fs,
-    io::{ErrorKind, Write},
-    path::PathBuf,
-};
-
-use crate::{error::Error, result::Result};
-
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
-pub struct Config {
-    pub model: ModelConfig,
-    pub gui: GuiConfig,
-}
-
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
-pub struct ModelConfig {
-    pub cuda: bool,
-}
-
-#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
-pub struct GuiConfig {
-    pub width: f32,
-    pub height: f32,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            model: ModelConfig { cuda: false },
-            gui: GuiConfig {
-                width: 350.,
-                height: 450.,
-            },
-        }
-    }
-}
-
-impl Config {
-    pub fn get() -> Result<Config> {
-        let config_dir = Self::get_config_dir()
-            .unwrap_or_else(|err| panic!("Failed getting config path with err: {}", err));
-
-        let config_str = match fs::read_to_string(config_dir.clone()) {
-            Ok(config) => config,
-            Err(err) => {
-                if err.kind() == ErrorKind::NotFound {
-                    return Self::upsert_new(config_dir);
-                }
-                return Ok(Self::default());
-            }
-        };
-
-        match config::Config::builder()
-            .add_source(config::File::from_str(
-                &config_str,
-                config::FileFormat::Json,
-            ))
-            .build()
-            .map_err(Error::ConfigError)?
-            .try_deserialize::<Config>()
-        {
-            Ok(cfg) => Ok(cfg),
-            Err(_) => Self::upsert_new(config_dir),
-        }
-    }
-
-    pub fn update_config_file(&mut self) -> Result<()> {
-        let config_dir = Self::get_config_dir()?;
-        let _ = Self::upsert_config_file(config_dir, self);
-        Ok(())
-    }
-
-    fn get_config_dir() -> Result<PathBuf> {
-        Ok(std::env::current_dir()
-            .map_err(Error::as_unknown_error)?
-            .join("config.json"))
-    }
-
-    fn upsert_new(config_dir: PathBuf) -> Result<Config> {
-        let config = Self::default();
-        Self::upsert_config_file(config_dir, &config)?;
-        Ok(config)
-    }
-
-    fn upsert_config_file(config_dir: PathBuf, config: &Config) -> Result<()> {
-        fs::File::create(config_dir)
-            .map_err(|err| Error::UnknownError(Box::new(err)))?
-            .write_all(
-                serde_json::to_string(config)
-                    .map_err(|err| Error::UnknownError(Box::new(err)))?
-                    .as_bytes(),
-            )
-            .map_err(|err| Error::UnknownError(Box::new(err)))
-    }
-}
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+
+use crate::{
+    cv::{CameraConfig, InputSource},
+    error::Error,
+    job::{BatchConfig, VideoConfig},
+    model::{
+        data::{AnnotationStyle, InputFormat, Normal, PoseLimits},
+        tracker::{DetectionSchedule, TrackerConfig},
+        DetectorConfig, RecognitionConfig, Suppression,
+    },
+    result::Result,
+    stream::StreamConfig,
+};
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct Config {
+    pub model: ModelConfig,
+    pub gui: GuiConfig,
+    /// Frames previewed & processed
+    #[serde(default)]
+    pub input: InputSource,
+    /// Capture device of `InputSource::Camera`
+    #[serde(default)]
+    pub camera: CameraConfig,
+    /// Output of processed video files
+    #[serde(default)]
+    pub video: VideoConfig,
+    /// Naming & resume of `noface batch` image runs
+    #[serde(default)]
+    pub batch: BatchConfig,
+    /// MJPEG over http of the processed frames
+    #[serde(default)]
+    pub stream: StreamConfig,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct ModelConfig {
+    pub cuda: bool,
+    #[serde(default)]
+    pub detect_input: InputFormat,
+    #[serde(default = "ModelConfig::default_swap_input")]
+    pub swap_input: InputFormat,
+    /// Range & order of the swapped face, set by the model rather than `swap_input`
+    #[serde(default = "ModelConfig::default_swap_output")]
+    pub swap_output: InputFormat,
+    #[serde(default)]
+    pub recognition_input: InputFormat,
+    /// Recognition model, embedding size & crop size
+    #[serde(default)]
+    pub recognition: RecognitionConfig,
+    /// Detection model & its output layout
+    #[serde(default)]
+    pub detector: DetectorConfig,
+    /// Draws detections onto output frames when set
+    #[serde(default)]
+    pub annotation: Option<AnnotationStyle>,
+    #[serde(default)]
+    pub tracker: TrackerConfig,
+    #[serde(default)]
+    pub detection: DetectionSchedule,
+    /// Merging of overlapping detections
+    #[serde(default)]
+    pub suppression: Suppression,
+    /// Fades out swaps of faces turned away from the camera, `null` turns it off
+    #[serde(default = "ModelConfig::default_pose_limits")]
+    pub pose_limits: Option<PoseLimits>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
+pub struct GuiConfig {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        Self {
+            cuda: false,
+            detect_input: InputFormat::default(),
+            swap_input: Self::default_swap_input(),
+            swap_output: Self::default_swap_output(),
+            recognition_input: InputFormat::default(),
+            recognition: RecognitionConfig::default(),
+            detector: DetectorConfig::default(),
+            annotation: None,
+            tracker: TrackerConfig::default(),
+            detection: DetectionSchedule::default(),
+            suppression: Suppression::default(),
+            pose_limits: Self::default_pose_limits(),
+        }
+    }
+}
+
+impl ModelConfig {
+    fn default_pose_limits() -> Option<PoseLimits> {
+        Some(PoseLimits::default())
+    }
+
+    fn default_swap_input() -> InputFormat {
+        InputFormat {
+            normal: Normal::ZeroToP1,
+            ..Default::default()
+        }
+    }
+
+    // inswapper writes 0 ~ 1 rgb
+    fn default_swap_output() -> InputFormat {
+        InputFormat {
+            normal: Normal::ZeroToP1,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            model: ModelConfig::default(),
+            gui: GuiConfig {
+                width: 350.,
+                height: 450.,
+            },
+            input: InputSource::default(),
+            camera: CameraConfig::default(),
+            video: VideoConfig::default(),
+            batch: BatchConfig::default(),
+            stream: StreamConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn get() -> Result<Config> {
+        let config_dir = Self::get_config_dir()
+            .unwrap_or_else(|err| panic!("Failed getting config path with err: {}", err));
+
+        let config_str = match fs::read_to_string(config_dir.clone()) {
+            Ok(config) => config,
+            Err(err) => {
+                if err.kind() == ErrorKind::NotFound {
+                    return Self::upsert_new(config_dir);
+                }
+                return Ok(Self::default());
+            }
+        };
+
+        match config::Config::builder()
+            .add_source(config::File::from_str(
+                &config_str,
+                config::FileFormat::Json,
+            ))
+            .build()
+            .map_err(Error::ConfigError)?
+            .try_deserialize::<Config>()
+        {
+            Ok(cfg) => Ok(cfg),
+            Err(_) => Self::upsert_new(config_dir),
+        }
+    }
+
+    pub fn update_config_file(&mut self) -> Result<()> {
+        let config_dir = Self::get_config_dir()?;
+        let _ = Self::upsert_config_file(config_dir, self);
+        Ok(())
+    }
+
+    fn get_config_dir() -> Result<PathBuf> {
+        Ok(std::env::current_dir()
+            .map_err(Error::as_unknown_error)?
+            .join("config.json"))
+    }
+
+    fn upsert_new(config_dir: PathBuf) -> Result<Config> {
+        let config = Self::default();
+        Self::upsert_config_file(config_dir, &config)?;
+        Ok(config)
+    }
+
+    fn upsert_config_file(config_dir: PathBuf, config: &Config) -> Result<()> {
+        fs::File::create(config_dir)
+            .map_err(|err| Error::UnknownError(Box::new(err)))?
+            .write_all(
+                serde_json::to_string(config)
+                    .map_err(|err| Error::UnknownError(Box::new(err)))?
+                    .as_bytes(),
+            )
+            .map_err(|err| Error::UnknownError(Box::new(err)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ModelConfig;
+
+    #[test]
+    fn missing_keys_match_model_defaults() {
+        let config: ModelConfig =
+            serde_json::from_str(r#"{ "cuda": false }"#).expect("Failed parsing config");
+        assert_eq!(config.pose_limits, ModelConfig::default().pose_limits);
+        assert_eq!(config.swap_input, ModelConfig::default().swap_input);
+        assert_eq!(config.swap_output, ModelConfig::default().swap_output);
+
+        let config: ModelConfig = serde_json::from_str(r#"{ "cuda": false, "pose_limits": null }"#)
+            .expect("Failed parsing config");
+        assert_eq!(config.pose_limits, None);
+    }
+}
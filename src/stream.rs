@@ -0,0 +1,401 @@
+// Processed frames served as MJPEG over http, for OBS browser sources & capture
+// https://en.wikipedia.org/wiki/Motion_JPEG#M-JPEG_over_HTTP
+// https://developer.mozilla.org/en-US/docs/Web/HTTP/Basics_of_HTTP/MIME_types#multipartx-mixed-replace
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{image::Image, model::Tensor, Error, Result};
+
+const BOUNDARY: &str = "noface_frame";
+// stalled clients are dropped after
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+// waiting clients are checked for a closed connection every
+const IDLE_PROBE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    pub enabled: bool,
+    /// Served on `http://127.0.0.1:<port>/`, 0 picks a free port
+    pub port: u16,
+    /// JPEG quality 1 ~ 100
+    pub quality: u8,
+    /// Further connections are refused with 503
+    pub max_clients: usize,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8090,
+            quality: 80,
+            max_clients: 8,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    // raw frame waiting for the encoder, newer frames replace it
+    pending: Mutex<Option<Tensor>>,
+    pending_ready: Condvar,
+    // (sequence, jpeg) of the last encoded frame
+    latest: Mutex<(u64, Arc<Vec<u8>>)>,
+    latest_ready: Condvar,
+    clients: AtomicUsize,
+    closed: AtomicBool,
+}
+
+pub struct MjpegStream {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl MjpegStream {
+    /// Binds localhost & starts the listener and encoder threads
+    #[tracing::instrument(name = "Starting MJPEG stream", err)]
+    pub fn start(config: &StreamConfig) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.port))
+            .map_err(Error::as_unknown_error)?;
+        let addr = listener.local_addr().map_err(Error::as_unknown_error)?;
+        let shared = Arc::new(Shared::default());
+
+        let encoder = {
+            let (shared, quality) = (Arc::clone(&shared), config.quality.clamp(1, 100));
+            thread::Builder::new()
+                .name("mjpeg_encoder".into())
+                .spawn(move || encode_loop(&shared, quality))
+                .map_err(Error::as_unknown_error)?
+        };
+        let accept = {
+            let (shared, max_clients) = (Arc::clone(&shared), config.max_clients);
+            thread::Builder::new()
+                .name("mjpeg_listener".into())
+                .spawn(move || accept_loop(listener, shared, max_clients))
+                .map_err(Error::as_unknown_error)?
+        };
+
+        tracing::info!("Streaming on http://{}/", addr);
+        Ok(Self {
+            addr,
+            shared,
+            threads: vec![encoder, accept],
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn clients(&self) -> usize {
+        self.shared.clients.load(Ordering::Relaxed)
+    }
+
+    /// Hands the frame to the encoder without waiting on it or any client, unwatched frames are dropped
+    pub fn publish(&self, frame: &Tensor) {
+        if self.clients() == 0 {
+            return;
+        }
+        if let Ok(mut pending) = self.shared.pending.lock() {
+            *pending = Some(frame.clone());
+            self.shared.pending_ready.notify_one();
+        }
+    }
+}
+
+impl Drop for MjpegStream {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.pending_ready.notify_all();
+        self.shared.latest_ready.notify_all();
+        // wakes the blocking accept
+        let _ = TcpStream::connect(self.addr);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn encode_loop(shared: &Shared, quality: u8) {
+    loop {
+        let frame = {
+            let Ok(pending) = shared.pending.lock() else {
+                return;
+            };
+            let Ok(mut pending) = shared.pending_ready.wait_while(pending, |frame| {
+                frame.is_none() && !shared.closed.load(Ordering::SeqCst)
+            }) else {
+                return;
+            };
+            match pending.take() {
+                Some(frame) if !shared.closed.load(Ordering::SeqCst) => frame,
+                _ => return,
+            }
+        };
+
+        match encode_jpeg(frame, quality) {
+            Ok(jpeg) => {
+                let Ok(mut latest) = shared.latest.lock() else {
+                    return;
+                };
+                *latest = (latest.0 + 1, Arc::new(jpeg));
+                shared.latest_ready.notify_all();
+            }
+            Err(err) => tracing::warn!("Failed encoding stream frame: {}", err),
+        }
+    }
+}
+
+fn encode_jpeg(frame: Tensor, quality: u8) -> Result<Vec<u8>> {
+    let image = Image::from(frame);
+    let mut jpeg = vec![];
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+        .encode_image(&image.0)
+        .map_err(Error::ImageError)?;
+    Ok(jpeg)
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>, max_clients: usize) {
+    for conn in listener.incoming() {
+        if shared.closed.load(Ordering::SeqCst) {
+            break;
+        }
+        let mut conn = match conn {
+            Ok(conn) => conn,
+            Err(err) => {
+                tracing::debug!("Failed accepting stream client: {}", err);
+                continue;
+            }
+        };
+        if shared.clients.load(Ordering::SeqCst) >= max_clients {
+            let _ = conn.write_all(
+                b"HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+            );
+            continue;
+        }
+
+        shared.clients.fetch_add(1, Ordering::SeqCst);
+        let shared = Arc::clone(&shared);
+        let spawned = thread::Builder::new()
+            .name("mjpeg_client".into())
+            .spawn(move || {
+                if let Err(err) = serve(conn, &shared) {
+                    tracing::debug!("Stream client left: {}", err);
+                }
+                shared.clients.fetch_sub(1, Ordering::SeqCst);
+            });
+        if let Err(err) = spawned {
+            tracing::warn!("Failed spawning stream client: {}", err);
+        }
+    }
+}
+
+// only `GET /` gets the stream, anything else like a browser's favicon request is a 404
+fn serve(conn: TcpStream, shared: &Shared) -> std::io::Result<()> {
+    conn.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    conn.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new(conn.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+        line.clear();
+    }
+
+    let mut conn = conn;
+    if !is_stream_request(&request) {
+        return conn.write_all(
+            b"HTTP/1.1 404 Not Found\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        );
+    }
+    write!(
+        conn,
+        "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        BOUNDARY
+    )?;
+
+    let poisoned = || std::io::Error::other("stream state poisoned");
+    let mut seen = 0;
+    loop {
+        let (seq, jpeg) = {
+            let latest = shared.latest.lock().map_err(|_| poisoned())?;
+            let (latest, wait) = shared
+                .latest_ready
+                .wait_timeout_while(latest, IDLE_PROBE, |(seq, _)| {
+                    *seq == seen && !shared.closed.load(Ordering::SeqCst)
+                })
+                .map_err(|_| poisoned())?;
+            if shared.closed.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            if wait.timed_out() {
+                // nothing published, frees the slot of clients that left meanwhile
+                drop(latest);
+                if is_closed(&conn)? {
+                    return Ok(());
+                }
+                continue;
+            }
+            latest.clone()
+        };
+        seen = seq;
+
+        write!(
+            conn,
+            "--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+            BOUNDARY,
+            jpeg.len()
+        )?;
+        conn.write_all(&jpeg)?;
+        conn.write_all(b"\r\n")?;
+    }
+}
+
+// `GET / HTTP/1.1`, query strings like cache busters are allowed
+fn is_stream_request(request: &str) -> bool {
+    let mut parts = request.split_ascii_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => target.split('?').next() == Some("/"),
+        _ => false,
+    }
+}
+
+// peer hung up, checked without writing into the stream
+fn is_closed(conn: &TcpStream) -> std::io::Result<bool> {
+    conn.set_nonblocking(true)?;
+    let closed = match conn.peek(&mut [0]) {
+        Ok(0) => Ok(true),
+        Ok(_) => Ok(false),
+        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err),
+    };
+    conn.set_nonblocking(false)?;
+    closed
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        time::{Duration, Instant},
+    };
+
+    use super::{is_stream_request, MjpegStream, StreamConfig};
+    use crate::{image::Image, model::Tensor};
+
+    #[test]
+    fn releases_clients_leaving_while_idle() {
+        let stream = MjpegStream::start(&StreamConfig {
+            enabled: true,
+            port: 0,
+            max_clients: 1,
+            ..Default::default()
+        })
+        .expect("Failed starting stream");
+
+        let wait_for = |clients: usize| {
+            let start = Instant::now();
+            while stream.clients() != clients && start.elapsed() < Duration::from_secs(5) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            stream.clients()
+        };
+
+        // reloads of an idle stream, each needs the single slot
+        for _ in 0..3 {
+            let mut client = TcpStream::connect(stream.addr()).expect("Failed connecting");
+            client
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .expect("Failed sending request");
+            assert_eq!(wait_for(1), 1, "Client wasn't accepted");
+            drop(client);
+            assert_eq!(wait_for(0), 0, "Idle client kept its slot");
+        }
+    }
+
+    #[test]
+    fn answers_other_paths_with_not_found() {
+        assert!(is_stream_request("GET / HTTP/1.1\r\n"));
+        assert!(is_stream_request("GET /?t=1 HTTP/1.1\r\n"));
+        assert!(!is_stream_request("GET /favicon.ico HTTP/1.1\r\n"));
+        assert!(!is_stream_request("POST / HTTP/1.1\r\n"));
+
+        let stream = MjpegStream::start(&StreamConfig {
+            enabled: true,
+            port: 0,
+            max_clients: 1,
+            ..Default::default()
+        })
+        .expect("Failed starting stream");
+        let mut client = TcpStream::connect(stream.addr()).expect("Failed connecting");
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("Failed setting timeout");
+        client
+            .write_all(b"GET /favicon.ico HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .expect("Failed sending request");
+
+        let mut response = String::new();
+        client
+            .read_to_string(&mut response)
+            .expect("Failed reading response");
+        assert!(response.starts_with("HTTP/1.1 404"), "Got {}", response);
+
+        let start = Instant::now();
+        while stream.clients() != 0 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(stream.clients(), 0, "Favicon request kept a slot");
+    }
+
+    #[test]
+    fn serves_latest_frame_as_mjpeg() {
+        let stream = MjpegStream::start(&StreamConfig {
+            enabled: true,
+            port: 0,
+            ..Default::default()
+        })
+        .expect("Failed starting stream");
+
+        let mut client = TcpStream::connect(stream.addr()).expect("Failed connecting");
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .expect("Failed setting timeout");
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .expect("Failed sending request");
+
+        let start = Instant::now();
+        while stream.clients() == 0 && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(stream.clients(), 1, "Client wasn't accepted");
+        stream.publish(&Tensor::from(Image::from(image::RgbImage::new(8, 8))));
+
+        let mut response = vec![];
+        let mut buf = [0; 1024];
+        // until the jpeg start of image marker shows up
+        while !response.windows(2).any(|w| w == [0xff, 0xd8]) {
+            let n = client.read(&mut buf).expect("Failed reading response");
+            assert!(n > 0, "Stream closed early");
+            response.extend_from_slice(&buf[..n]);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "Got {}", response);
+        assert!(response.contains("multipart/x-mixed-replace; boundary=noface_frame"));
+        assert!(response.contains("--noface_frame\r\nContent-Type: image/jpeg"));
+    }
+}
@@ -0,0 +1,35 @@
+// Fixtures shared by the in-file tests
+
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+/// Fresh directory under the system temp dir, removed with its contents on drop,
+/// so a failing assert doesn't leave it behind
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "noface_{}_{}",
+            prefix,
+            rand::thread_rng().gen::<u32>()
+        ));
+        std::fs::create_dir_all(&path).expect("Failed to create temp dir");
+        Self(path)
+    }
+}
+
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}